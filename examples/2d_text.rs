@@ -1,9 +1,9 @@
 use bevy::prelude::*;
-use bevy_text_mesh::TextMeshPlugin;
+use bevy_text_mesh::prelude::*;
 
 fn main() {
     App::new()
-        .add_plugins((DefaultPlugins, TextMeshPlugin)) // TextMeshPlugin for interop check
+        .add_plugins((DefaultPlugins, TextMeshPlugin))
         .add_systems(Startup, setup)
         .add_systems(Update, animate_rotation)
         .run();
@@ -29,12 +29,37 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
             ..default()
         })
         .insert(AnimateRotation);
+
+    // mesh text rendered through the 2d pipeline, sorted with other 2d entities by z
+    commands
+        .spawn(TextMesh2dBundle {
+            text_mesh: TextMesh {
+                text: String::from("mesh text in 2d"),
+                style: TextMeshStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf#mesh"),
+                    font_size: SizeUnit::World(60.),
+                    ..Default::default()
+                },
+                size: TextMeshSize {
+                    width: SizeUnit::World(600.),
+                    height: SizeUnit::World(100.),
+                    depth: None,
+                    ..Default::default()
+                },
+                alignment: TextMeshAlignment {
+                    horizontal: HorizontalAlign::Center,
+                    vertical: VerticalAlign::Center,
+                    // rotates around the center of the box
+                    anchor: TextMeshAnchor::Center,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(AnimateRotation);
 }
 
-fn animate_rotation(
-    time: Res<Time>,
-    mut query: Query<&mut Transform, (With<Text>, With<AnimateRotation>)>,
-) {
+fn animate_rotation(time: Res<Time>, mut query: Query<&mut Transform, With<AnimateRotation>>) {
     for mut transform in &mut query {
         transform.rotation = Quat::from_rotation_z(time.elapsed_seconds_f64().cos() as f32);
     }
@@ -77,7 +77,7 @@ fn update_text_mesh(
 ) {
     if timer.timer.tick(time.delta()).just_finished() {
         for mut text_mesh in text_meshes.iter_mut() {
-            let updated_text = String::from(format!("Time = {:.3}", time.elapsed_seconds_f64()));
+            let updated_text = format!("Time = {:.3}", time.elapsed_seconds_f64());
 
             if text_mesh.text != updated_text {
                 text_mesh.text = updated_text;
@@ -93,9 +93,9 @@ fn rotate_camera(mut camera: Query<&mut Transform, With<Camera>>, time: Res<Time
         let distance = 3.5;
 
         camera.translation = Vec3::new(
-            angle.sin() as f32 * distance,
+            angle.sin() * distance,
             camera.translation.y,
-            angle.cos() as f32 * distance,
+            angle.cos() * distance,
         );
 
         *camera = camera.looking_at(Vec3::new(0.0, 1.5, 0.), Vec3::Y);
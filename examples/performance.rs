@@ -32,7 +32,7 @@ fn main() {
         .add_plugins((
             DefaultPlugins,
             TextMeshPlugin,
-            FrameTimeDiagnosticsPlugin::default(),
+            FrameTimeDiagnosticsPlugin,
             LogDiagnosticsPlugin::default(),
         ))
         .register_diagnostic(Diagnostic::new(TEXT_MESH_UPDATES, "text_mesh_updates", 20))
@@ -61,7 +61,7 @@ struct UpdateTimer {
 struct EngineTime;
 
 #[derive(Component)]
-struct FPS;
+struct Fps;
 
 #[derive(Component)]
 struct TextCount;
@@ -100,7 +100,7 @@ fn setup_text_mesh(
             transform: Transform::from_xyz(0.0, 2.5, 0.),
             ..Default::default()
         })
-        .insert(FPS);
+        .insert(Fps);
 
     commands
         .spawn(TextMeshBundle {
@@ -194,7 +194,7 @@ fn update_text_mesh(
     let mut update_count = 0;
     if timer.text_update_timer.tick(time.delta()).just_finished() {
         for mut text_mesh in text_meshes.iter_mut() {
-            let updated_text = String::from(format!("Time = {:.3}", time.elapsed_seconds_f64()));
+            let updated_text = format!("Time = {:.3}", time.elapsed_seconds_f64());
 
             if text_mesh.text != updated_text {
                 text_mesh.text = updated_text;
@@ -214,20 +214,21 @@ fn rotate_camera(mut camera: Query<&mut Transform, With<Camera>>, time: Res<Time
         let distance = 6.5;
 
         camera.translation = Vec3::new(
-            angle.sin() as f32 * distance,
+            angle.sin() * distance,
             camera.translation.y,
-            angle.cos() as f32 * distance,
+            angle.cos() * distance,
         );
 
         *camera = camera.looking_at(Vec3::new(0.0, 1.5, 0.), Vec3::Y);
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn update_frame_rate(
     diagnostics: Res<DiagnosticsStore>,
     time: Res<Time>,
     mut timer: ResMut<UpdateTimer>,
-    mut fps_text: Query<(Entity, &mut TextMesh, Option<&FPS>), Or<(With<FPS>, With<TextCount>)>>,
+    mut fps_text: Query<(Entity, &mut TextMesh, Option<&Fps>), Or<(With<Fps>, With<TextCount>)>>,
     camera_entity: Query<Entity, With<Camera>>,
     mut transform_query: Query<&mut Transform>,
     state: Res<SceneState>,
@@ -246,7 +247,7 @@ fn update_frame_rate(
         }
 
         let camera_entity = camera_entity.iter().next().unwrap();
-        let camera_transform = transform_query.get_mut(camera_entity).unwrap().clone();
+        let camera_transform = *transform_query.get_mut(camera_entity).unwrap();
         let mut transform = transform_query.get_mut(text_mesh_entity).unwrap();
 
         // eh - why negative?
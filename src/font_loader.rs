@@ -2,6 +2,7 @@ use bevy::asset::AsyncReadExt;
 use bevy::text::Font;
 use std::error::Error;
 use std::fmt::Display;
+use std::sync::Mutex;
 
 use anyhow::Result;
 use bevy::asset::io::Reader;
@@ -15,7 +16,7 @@ impl Error for FontLoaderError {}
 
 impl Display for FontLoaderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.to_string())
+        f.write_str("unable to load font")
     }
 }
 
@@ -41,14 +42,11 @@ impl AssetLoader for FontLoader {
                 .expect("unable to read font");
 
             // ttf fontloading
-            let font = TextMeshFont {
-                ttf_font: ttf2mesh::TTFFile::from_buffer_vec(bytes.clone())
-                    .expect("unable to decode asset"),
-            };
+            let font = TextMeshFont::from_bytes(bytes.clone()).expect("unable to decode asset");
 
             load_context.add_labeled_asset("mesh".into(), font);
 
-            let original_font = Font::try_from_bytes(bytes.into()).expect("unable to read font");
+            let original_font = Font::try_from_bytes(bytes).expect("unable to read font");
 
             Ok(original_font)
         })
@@ -62,7 +60,30 @@ impl AssetLoader for FontLoader {
 #[derive(TypeUuid, TypePath, Asset)]
 #[uuid = "5415ac03-d009-471e-89ab-dc0d4e31a8c4"]
 pub struct TextMeshFont {
-    pub(crate) ttf_font: ttf2mesh::TTFFile,
+    /// Glyph tessellator, locked for the tessellation of a glyph
+    pub(crate) ttf_font: Mutex<ttf2mesh::TTFFile>,
+
+    /// Raw font data, parsed on demand for the font metrics (advances, kerning, ...)
+    pub(crate) data: Vec<u8>,
+}
+
+impl TextMeshFont {
+    pub(crate) fn from_bytes(bytes: Vec<u8>) -> Result<Self, FontLoaderError> {
+        // validate once, so that later `face()` calls can not fail
+        ttf_parser::Face::parse(&bytes, 0).map_err(|_| FontLoaderError)?;
+
+        Ok(Self {
+            ttf_font: Mutex::new(
+                ttf2mesh::TTFFile::from_buffer_vec(bytes.clone()).map_err(|_| FontLoaderError)?,
+            ),
+            data: bytes,
+        })
+    }
+
+    /// Parsed font metrics
+    pub(crate) fn face(&self) -> ttf_parser::Face<'_> {
+        ttf_parser::Face::parse(&self.data, 0).expect("font data validated at load time")
+    }
 }
 
 impl std::fmt::Debug for TextMeshFont {
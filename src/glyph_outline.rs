@@ -0,0 +1,349 @@
+use std::{
+    alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout},
+    f32::consts::{PI, TAU},
+    marker::PhantomData,
+    os::raw::c_int,
+    slice,
+};
+
+use bevy::prelude::*;
+use i_overlay::{
+    core::fill_rule::FillRule,
+    float::simplify::SimplifyShape,
+    mesh::{
+        outline::offset::OutlineOffset,
+        style::{LineJoin, OutlineStyle},
+    },
+};
+use ttf2mesh::Quality;
+use ttf2mesh_sys as sys;
+use ttf_parser::{Face, GlyphId, OutlineBuilder};
+
+use crate::mesh_cache::{quality_level, GlyphMesh};
+
+/// Glyph outline in em units, for modifying the outline before the tessellation
+/// by ttf2mesh
+#[derive(Debug, Default)]
+pub(crate) struct GlyphOutline {
+    contours: Vec<Vec<OutlinePoint>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OutlinePoint {
+    position: Vec2,
+    on_curve: bool,
+}
+
+impl GlyphOutline {
+    /// Outline of the glyph, or `None` for glyphs without an outline
+    pub(crate) fn new(face: &Face, glyph_id: GlyphId) -> Option<Self> {
+        let mut builder = OutlineCollector {
+            scale: 1. / face.units_per_em() as f32,
+            outline: GlyphOutline::default(),
+        };
+        face.outline_glyph(glyph_id, &mut builder)?;
+
+        let mut outline = builder.outline;
+        for contour in &mut outline.contours {
+            // closing points are implicit, and coincident points have no direction
+            contour.dedup_by(|b, a| b.position == a.position);
+            if contour.len() > 1
+                && contour.first().unwrap().position == contour.last().unwrap().position
+            {
+                contour.pop();
+            }
+        }
+        outline.contours.retain(|contour| contour.len() > 2);
+
+        Some(outline)
+    }
+
+    /// Dilates the outline by `strength` (em units) on every side, and moves it
+    /// right by the same amount, keeping the side bearings of the widened advance.
+    ///
+    /// The curves are linearized at the tessellation `quality` first. Contours
+    /// that grow into each other, or into themselves, are merged, so that the
+    /// dilated outline stays a valid input for the tessellation
+    pub(crate) fn embolden(&mut self, strength: f32, quality: Quality) {
+        let contours = self
+            .contours
+            .iter()
+            .map(|contour| {
+                linearize(contour, quality)
+                    .into_iter()
+                    .map(|point| point.to_array())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // the outer contours are clockwise in TrueType fonts, and may overlap
+        let shapes = contours.simplify_shape(FillRule::NonZero);
+        let style = OutlineStyle::new(strength).line_join(LineJoin::Miter(PI / 8.));
+
+        self.contours = shapes
+            .outline(&style)
+            .into_iter()
+            .flatten()
+            .map(|contour| {
+                contour
+                    .into_iter()
+                    .map(|[x, y]| OutlinePoint {
+                        position: Vec2::new(x + strength, y),
+                        on_curve: true,
+                    })
+                    .collect()
+            })
+            .collect();
+    }
+
+    /// Tessellates the outline into a 3d mesh of unit depth, or into a flat mesh
+    /// facing +z. Empty outlines produce an empty mesh
+    pub(crate) fn tessellate(&self, quality: Quality, extruded: bool) -> GlyphMesh {
+        let mut glyph_mesh = GlyphMesh::default();
+        if self.contours.is_empty() {
+            return glyph_mesh;
+        }
+
+        let mut points = self
+            .contours
+            .iter()
+            .flatten()
+            .map(|point| RawPoint {
+                x: point.position.x,
+                y: point.position.y,
+                flags: if point.on_curve {
+                    RAW_POINT_ON_CURVE
+                } else {
+                    0
+                },
+            })
+            .collect::<Vec<_>>();
+        let outline = RawOutline::new(&self.contours, &mut points);
+
+        // the tessellation reads the outline only
+        let mut glyph: sys::ttf_glyph = unsafe { std::mem::zeroed() };
+        glyph.outline = outline.ptr.cast();
+
+        let quality = quality_level(quality);
+        let features = sys::TTF_FEATURES_DFLT as c_int;
+
+        let faces = match extruded {
+            true => unsafe {
+                let mut mesh = std::ptr::null_mut();
+                if sys::ttf_glyph2mesh3d(&mut glyph, &mut mesh, quality, features, 1.)
+                    != sys::TTF_DONE as c_int
+                {
+                    return glyph_mesh;
+                }
+
+                let nvert = (*mesh).nvert as usize;
+                for vertex in slice::from_raw_parts((*mesh).vert, nvert) {
+                    glyph_mesh.vertices.push([vertex.x, vertex.y, vertex.z]);
+                }
+                for normal in slice::from_raw_parts((*mesh).normals, nvert) {
+                    glyph_mesh.normals.push([normal.x, normal.y, normal.z]);
+                }
+                let faces = slice::from_raw_parts((*mesh).faces, (*mesh).nfaces as usize)
+                    .iter()
+                    .map(|face| (face.v1, face.v2, face.v3))
+                    .collect::<Vec<_>>();
+
+                sys::ttf_free_mesh3d(mesh);
+                faces
+            },
+            false => unsafe {
+                let mut mesh = std::ptr::null_mut();
+                if sys::ttf_glyph2mesh(&mut glyph, &mut mesh, quality, features)
+                    != sys::TTF_DONE as c_int
+                {
+                    return glyph_mesh;
+                }
+
+                for vertex in slice::from_raw_parts((*mesh).vert, (*mesh).nvert as usize) {
+                    glyph_mesh.vertices.push([vertex.x, vertex.y, 0.]);
+                    glyph_mesh.normals.push([0., 0., 1.]);
+                }
+                let faces = slice::from_raw_parts((*mesh).faces, (*mesh).nfaces as usize)
+                    .iter()
+                    .map(|face| (face.v1, face.v2, face.v3))
+                    .collect::<Vec<_>>();
+
+                sys::ttf_free_mesh(mesh);
+                faces
+            },
+        };
+
+        for (a, b, c) in faces {
+            glyph_mesh
+                .indices
+                .extend_from_slice(&[a as u32, b as u32, c as u32]);
+        }
+
+        glyph_mesh
+    }
+}
+
+/// Points of a closed contour, with the curves replaced by line segments. The
+/// number of segments follows the turn of the curve, as in ttf2mesh
+fn linearize(contour: &[OutlinePoint], quality: Quality) -> Vec<Vec2> {
+    let quality = quality_level(quality) as f32;
+    let mut points = Vec::new();
+
+    for (i, point) in contour.iter().enumerate() {
+        if point.on_curve {
+            points.push(point.position);
+            continue;
+        }
+
+        // the outline collector places an on-curve point on both sides of a control point
+        let start = contour[(i + contour.len() - 1) % contour.len()].position;
+        let end = contour[(i + 1) % contour.len()].position;
+        let (start_tangent, end_tangent) = (point.position - start, end - point.position);
+
+        let sin = start_tangent
+            .normalize_or_zero()
+            .perp_dot(end_tangent.normalize_or_zero());
+        let segments = (sin.abs().min(1.).asin() / TAU * quality).round() as usize + 1;
+
+        for step in 1..segments {
+            let t = step as f32 / segments as f32;
+            let point = start
+                .lerp(point.position, t)
+                .lerp(point.position.lerp(end, t), t);
+            points.push(point);
+        }
+    }
+
+    points
+}
+
+/// Collects the outline of a glyph. TrueType outlines are quadratic, cubic curves
+/// (of CFF fonts) are approximated by a single quadratic curve
+struct OutlineCollector {
+    scale: f32,
+    outline: GlyphOutline,
+}
+
+impl OutlineCollector {
+    fn push(&mut self, x: f32, y: f32, on_curve: bool) {
+        let point = OutlinePoint {
+            position: Vec2::new(x, y) * self.scale,
+            on_curve,
+        };
+        if let Some(contour) = self.outline.contours.last_mut() {
+            contour.push(point);
+        }
+    }
+
+    fn last(&self) -> Vec2 {
+        self.outline
+            .contours
+            .last()
+            .and_then(|contour| contour.last())
+            .map(|point| point.position / self.scale)
+            .unwrap_or_default()
+    }
+}
+
+impl OutlineBuilder for OutlineCollector {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.outline.contours.push(Vec::new());
+        self.push(x, y, true);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.push(x, y, true);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.push(x1, y1, false);
+        self.push(x, y, true);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let control =
+            (3. * (Vec2::new(x1, y1) + Vec2::new(x2, y2)) - self.last() - Vec2::new(x, y)) / 4.;
+        self.quad_to(control.x, control.y, x, y);
+    }
+
+    fn close(&mut self) {}
+}
+
+/// `ttf_point_t` of ttf2mesh, the `onc` flag is the second bit of the bitfield
+#[repr(C)]
+struct RawPoint {
+    x: f32,
+    y: f32,
+    flags: u32,
+}
+
+const RAW_POINT_ON_CURVE: u32 = 1 << 1;
+
+/// Contour of `ttf_outline_t`
+#[repr(C)]
+struct RawContour {
+    length: c_int,
+    subglyph_id: c_int,
+    subglyph_order: c_int,
+    pt: *mut RawPoint,
+}
+
+/// `ttf_outline_t` of ttf2mesh, followed by its contours in the same allocation
+#[repr(C)]
+struct RawOutlineHeader {
+    total_points: c_int,
+    ncontours: c_int,
+    cont: [RawContour; 0],
+}
+
+/// Outline allocation, pointing to the points of the caller
+struct RawOutline<'a> {
+    ptr: *mut RawOutlineHeader,
+    layout: Layout,
+    _points: PhantomData<&'a mut [RawPoint]>,
+}
+
+impl<'a> RawOutline<'a> {
+    fn new(contours: &[Vec<OutlinePoint>], points: &'a mut [RawPoint]) -> Self {
+        let layout = Layout::new::<RawOutlineHeader>()
+            .extend(Layout::array::<RawContour>(contours.len()).unwrap())
+            .unwrap()
+            .0
+            .pad_to_align();
+
+        unsafe {
+            let ptr = alloc_zeroed(layout) as *mut RawOutlineHeader;
+            if ptr.is_null() {
+                handle_alloc_error(layout);
+            }
+
+            (*ptr).total_points = points.len() as c_int;
+            (*ptr).ncontours = contours.len() as c_int;
+
+            // all contours are of the same subglyph, for the detection of holes
+            let mut start = 0;
+            let cont = std::ptr::addr_of_mut!((*ptr).cont) as *mut RawContour;
+            for (i, contour) in contours.iter().enumerate() {
+                cont.add(i).write(RawContour {
+                    length: contour.len() as c_int,
+                    subglyph_id: 0,
+                    subglyph_order: 0,
+                    pt: points[start..].as_mut_ptr(),
+                });
+                start += contour.len();
+            }
+
+            Self {
+                ptr,
+                layout,
+                _points: PhantomData,
+            }
+        }
+    }
+}
+
+impl Drop for RawOutline<'_> {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.ptr as *mut u8, self.layout) }
+    }
+}
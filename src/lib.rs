@@ -6,20 +6,21 @@ extern crate bitflags;
 use bevy::prelude::*;
 
 mod font_loader;
+mod glyph_outline;
 mod mesh_cache;
 mod mesh_data_generator;
 mod mesh_system;
+mod text_layout;
 mod text_mesh;
 
 pub mod prelude {
     pub use crate::font_loader::TextMeshFont;
+    pub use crate::mesh_cache::{MeshCache, DEFAULT_CACHE_MAX_BYTES};
     pub use crate::text_mesh::*;
     pub use crate::TextMeshPlugin;
-    pub use glyph_brush_layout::{HorizontalAlign, VerticalAlign};
 }
 
 use font_loader::FontLoader;
-use mesh_cache::MeshCache;
 pub use prelude::*;
 
 pub struct TextMeshPlugin;
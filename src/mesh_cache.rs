@@ -1,57 +1,230 @@
-use std::{collections::HashMap, hash::Hash};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
 
 use bevy::prelude::*;
+use ttf2mesh::Quality;
+use ttf_parser::GlyphId;
 
-// TODO: add accuracy to depth cache
-// TODO: purge cached entries, keep count per depth, and if it reaches zero
-// TODO: actually cache meshdata
+use crate::font_loader::TextMeshFont;
 
+/// Glyph meshes are cached per font, tessellation quality and mesh type
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub struct CacheKey {
-    char: char,
+    pub(crate) font: AssetId<TextMeshFont>,
+    pub(crate) glyph_id: GlyphId,
+    quality: u8,
     mesh_type: MeshType,
+
+    /// Synthetic emboldening and skew, as bits of the f32 values
+    synthesis: [u32; 2],
 }
 
 impl CacheKey {
-    pub(crate) fn new_3d(char: char, depth: f32) -> Self {
+    pub(crate) fn new_2d(font: AssetId<TextMeshFont>, glyph_id: GlyphId, quality: Quality) -> Self {
+        Self {
+            font,
+            glyph_id,
+            quality: quality_level(quality),
+            mesh_type: MeshType::Mesh2d,
+            synthesis: [0; 2],
+        }
+    }
+
+    /// Key of an extruded glyph, which is cached at unit depth and shared by
+    /// all depths
+    pub(crate) fn new_3d(font: AssetId<TextMeshFont>, glyph_id: GlyphId, quality: Quality) -> Self {
         Self {
-            char,
-            mesh_type: MeshType::Mesh3d(Depth(depth)),
+            font,
+            glyph_id,
+            quality: quality_level(quality),
+            mesh_type: MeshType::Mesh3d,
+            synthesis: [0; 2],
         }
     }
+
+    pub(crate) fn with_synthesis(mut self, embolden: f32, skew: f32) -> Self {
+        self.synthesis = [embolden.to_bits(), skew.to_bits()];
+        self
+    }
+}
+
+/// Same levels as used by ttf2mesh, `Quality` itself is not hashable
+pub(crate) fn quality_level(quality: Quality) -> u8 {
+    match quality {
+        Quality::Low => 10,
+        Quality::Medium => 20,
+        Quality::High => 50,
+        Quality::Custom(value) => value.clamp(8, 128),
+    }
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub enum MeshType {
-    // Mesh2d,
-    Mesh3d(Depth),
+    Mesh2d,
+    Mesh3d,
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub struct Depth(f32);
+/// Issue of the text mesh generation, logged once per [`MeshCache`]
+#[derive(Debug, Hash, PartialEq, Eq)]
+pub(crate) enum Warning {
+    /// Char missing from the font and its fallback fonts
+    MissingChar(AssetId<TextMeshFont>, char),
+    AutoDepth,
+}
 
-impl Hash for Depth {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        let val = (self.0 * 100.) as usize;
-        val.hash(state);
+/// Tessellated glyph in em units, relative to the glyph origin on the baseline
+#[derive(Debug, Default, Clone)]
+pub(crate) struct GlyphMesh {
+    pub vertices: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+}
+
+impl GlyphMesh {
+    fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(self.vertices.as_slice())
+            + std::mem::size_of_val(self.normals.as_slice())
+            + std::mem::size_of_val(self.indices.as_slice())
     }
 }
 
-// FIXME uhm, empty?
-impl Eq for Depth {}
+pub(crate) struct CachedGlyph {
+    mesh: GlyphMesh,
+    last_used: u64,
+}
+
+/// Default [`MeshCache::max_bytes`] budget
+pub const DEFAULT_CACHE_MAX_BYTES: usize = 64 * 1024 * 1024;
 
+/// Cache of tessellated glyphs, shared by all text meshes.
+///
+/// When the cache grows over its budget, the least recently used glyphs are
+/// evicted. Glyphs of the text mesh being generated are never evicted, so the
+/// budget may be exceeded temporarily. To configure the budget, insert the
+/// resource before adding the [`TextMeshPlugin`](crate::TextMeshPlugin), or
+/// modify it at runtime.
 #[derive(Resource)]
 pub struct MeshCache {
-    pub(crate) meshes: HashMap<CacheKey, ttf2mesh::Mesh<'static, ttf2mesh::Mesh3d>>,
+    /// Maximum number of cached glyphs, unlimited if `None`
+    pub max_glyphs: Option<usize>,
+
+    /// Maximum memory used by the cached glyph meshes, unlimited if `None`
+    pub max_bytes: Option<usize>,
+
+    pub(crate) meshes: HashMap<CacheKey, CachedGlyph>,
+    bytes: usize,
+    generation: u64,
+
+    /// Warnings already logged, so that regenerated text meshes do not repeat them
+    warnings: HashSet<Warning>,
 }
 
 impl Default for MeshCache {
     fn default() -> Self {
         Self {
+            max_glyphs: None,
+            max_bytes: Some(DEFAULT_CACHE_MAX_BYTES),
             meshes: HashMap::new(),
+            bytes: 0,
+            generation: 0,
+            warnings: HashSet::new(),
         }
     }
 }
 
-unsafe impl Send for MeshCache {} // FIXME: verify soundness
-unsafe impl Sync for MeshCache {} // FIXME: verify soundness
+impl MeshCache {
+    /// Number of cached glyphs
+    pub fn len(&self) -> usize {
+        self.meshes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meshes.is_empty()
+    }
+
+    /// Memory used by the cached glyph meshes
+    pub fn size_in_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Removes all cached glyphs
+    pub fn clear(&mut self) {
+        self.meshes.clear();
+        self.bytes = 0;
+    }
+
+    /// Removes the cached glyphs of a font
+    pub fn clear_font(&mut self, font: impl Into<AssetId<TextMeshFont>>) {
+        let font = font.into();
+        let bytes = &mut self.bytes;
+
+        self.meshes.retain(|key, glyph| {
+            if key.font == font {
+                *bytes -= glyph.mesh.size_in_bytes();
+            }
+            key.font != font
+        });
+        self.warnings
+            .retain(|warning| !matches!(warning, Warning::MissingChar(id, _) if *id == font));
+    }
+
+    /// True if the warning has not been logged yet
+    pub(crate) fn first_warning(&mut self, warning: Warning) -> bool {
+        self.warnings.insert(warning)
+    }
+
+    /// Starts the generation of a new text mesh: glyphs used after this
+    /// call are protected from eviction until the next call
+    pub(crate) fn next_generation(&mut self) {
+        self.generation += 1;
+    }
+
+    pub(crate) fn get_or_insert_with(
+        &mut self,
+        key: CacheKey,
+        tessellate: impl FnOnce() -> GlyphMesh,
+    ) -> &GlyphMesh {
+        let bytes = &mut self.bytes;
+        let glyph = self.meshes.entry(key).or_insert_with(|| {
+            let mesh = tessellate();
+            *bytes += mesh.size_in_bytes();
+
+            CachedGlyph { mesh, last_used: 0 }
+        });
+
+        glyph.last_used = self.generation;
+        &glyph.mesh
+    }
+
+    /// Evicts the least recently used glyphs, until the cache is within its budget
+    pub(crate) fn evict(&mut self) {
+        let over_budget = |cache: &Self| {
+            cache.max_glyphs.is_some_and(|max| cache.meshes.len() > max)
+                || cache.max_bytes.is_some_and(|max| cache.bytes > max)
+        };
+
+        if !over_budget(self) {
+            return;
+        }
+
+        let mut candidates = self
+            .meshes
+            .iter()
+            .filter(|(_, glyph)| glyph.last_used < self.generation)
+            .map(|(key, glyph)| (glyph.last_used, key.clone()))
+            .collect::<Vec<_>>();
+        candidates.sort_unstable_by_key(|(last_used, _)| *last_used);
+
+        for (_, key) in candidates {
+            if !over_budget(self) {
+                break;
+            }
+
+            if let Some(glyph) = self.meshes.remove(&key) {
+                self.bytes -= glyph.mesh.size_in_bytes();
+            }
+        }
+    }
+}
@@ -1,16 +1,85 @@
+use std::ops::Range;
+
 use bevy::prelude::*;
-use ttf2mesh::{TTFFile, Value};
+use ttf2mesh::{Quality, Value};
 
 use crate::{
-    mesh_cache::{CacheKey, MeshCache},
-    text_mesh::{FontStyle, TextMesh},
+    font_loader::TextMeshFont,
+    glyph_outline::GlyphOutline,
+    mesh_cache::{CacheKey, GlyphMesh, MeshCache, Warning},
+    text_layout::{fit_font_size, layout_text, shape_sections, LayoutSection},
+    text_mesh::{
+        FontStyle, TextMesh, TextMeshColors, TextMeshSection, TextMeshUvMode, DEFAULT_FONT_SCALAR,
+    },
 };
 
+#[derive(Default)]
 pub(crate) struct MeshData {
     pub vertices: Vec<[f32; 3]>,
     pub normals: Vec<[f32; 3]>,
     pub indices: Vec<u32>,
     pub uvs: Vec<[f32; 2]>,
+    pub colors: Vec<[f32; 4]>,
+}
+
+impl MeshData {
+    /// Appends a glyph mesh, scaled by `scale` and moved to `position`.
+    /// Returns the range of the added vertices
+    fn append(
+        &mut self,
+        mesh: &GlyphMesh,
+        scale: Vec3,
+        position: Vec2,
+        color: [f32; 4],
+    ) -> Range<usize> {
+        let offset = self.vertices.len();
+
+        for [x, y, z] in &mesh.vertices {
+            self.vertices.push([
+                x * scale.x + position.x,
+                y * scale.y + position.y,
+                z * scale.z,
+            ]);
+        }
+
+        self.normals.extend_from_slice(&mesh.normals);
+        self.colors.resize(self.vertices.len(), color);
+        self.indices
+            .extend(mesh.indices.iter().map(|index| index + offset as u32));
+
+        offset..self.vertices.len()
+    }
+
+    fn translate(&mut self, offset: Vec2) {
+        for vertex in &mut self.vertices {
+            vertex[0] += offset.x;
+            vertex[1] += offset.y;
+        }
+    }
+
+    /// Rotates the vertices by a quarter turn clockwise around the `origin`
+    fn rotate_clockwise(&mut self, range: Range<usize>, origin: Vec2) {
+        for vertex in &mut self.vertices[range.clone()] {
+            let [x, y] = [vertex[0] - origin.x, vertex[1] - origin.y];
+            vertex[0] = origin.x + y;
+            vertex[1] = origin.y - x;
+        }
+        for normal in &mut self.normals[range] {
+            *normal = [normal[1], -normal[0], normal[2]];
+        }
+    }
+}
+
+/// A run of a section, laid out with a single font
+struct LayoutRun {
+    /// Index of the section the run belongs to
+    section: usize,
+    font_id: AssetId<TextMeshFont>,
+    text: String,
+    font_style: FontStyle,
+
+    /// Bold and italic styles to synthesize
+    synthesized: FontStyle,
 }
 
 // FIXME: add validator, that validates all .unwrap's() at addition time
@@ -20,7 +89,7 @@ pub(crate) struct MeshData {
 // from the existing mesh
 pub(crate) fn generate_text_mesh(
     text_mesh: &TextMesh,
-    font: &mut TTFFile,
+    fonts: &Assets<TextMeshFont>,
     cache: Option<&mut MeshCache>,
 ) -> MeshData {
     trace!("Generate text mesh: {:?}", text_mesh.text);
@@ -36,163 +105,524 @@ pub(crate) fn generate_text_mesh(
     };
 
     // TODO performance: pre-allocate capacity
-    let mut vertices = Vec::new(); //with_capacity(4308); // TODO: allocate opportunistically
-    let mut normals = Vec::new(); //with_capacity(4308); // TODO: allocate opportunistically
-    let mut indices = Vec::new(); //with_capacity(8520);
-
-    let mut vertices_offset: usize = 0;
+    let mut mesh_data = MeshData::default();
+    let mut glyph_ranges = Vec::new();
 
-    let depth = 0.08;
+    let style = &text_mesh.style;
 
-    let text = if text_mesh.style.font_style.contains(FontStyle::UPPERCASE) {
-        text_mesh.text.to_uppercase()
-    } else if text_mesh.style.font_style.contains(FontStyle::LOWERCASE) {
-        text_mesh.text.to_lowercase()
+    // plain text is a single section, styled by the text mesh style
+    let plain_text;
+    let sections = if text_mesh.sections.is_empty() {
+        plain_text = TextMeshSection::new(&text_mesh.text); // TODO performance - extra allocation
+        std::slice::from_ref(&plain_text)
     } else {
-        text_mesh.text.clone() // TODO performance - extra allocation
+        text_mesh.sections.as_slice()
     };
 
-    let scalar = match text_mesh.style.font_size.as_scalar() {
-        Some(scalar) => scalar,
-        None => todo!("Font automatic sizing has not been implemented yet"),
-    };
+    // bold and italic faces of the font families, anything else is synthesized
+    let section_fonts = sections
+        .iter()
+        .map(|section| {
+            let font = section.style.font.as_ref().unwrap_or(&style.font);
+            let font_style = section.style.font_style.as_ref();
+            let font_style = font_style.unwrap_or(&style.font_style);
+
+            style
+                .font_families
+                .iter()
+                .find_map(|family| family.select(font, font_style))
+                .unwrap_or((font, font_style.clone()))
+        })
+        .collect::<Vec<_>>();
 
-    let spacing = Vec2::new(0.08, 0.1) * scalar;
-
-    let mut scaled_offset = Vec2::ZERO;
-    let mut scaled_row_y_max_height = 0.;
-
-    //println!("scalar={}, spacing={}", scalar, spacing);
-    for char in text.chars() {
-        //println!("{} offset={}", char, scaled_offset);
-        if char == ' ' {
-            scaled_offset.x += 0.2 * scalar + spacing.x;
-            continue;
-        } else if char == '\n' {
-            scaled_offset.x = 0.;
-            scaled_offset.y -= scaled_row_y_max_height + spacing.y;
-            continue;
+    let mut faces = Vec::new();
+    let all_fonts = section_fonts.iter().map(|(font, _)| *font);
+    for font_id in all_fonts.chain(&style.fallback_fonts).map(Handle::id) {
+        if !faces.iter().any(|(id, _)| *id == font_id) {
+            let font = fonts.get(font_id).expect("text mesh fonts are loaded");
+            faces.push((font_id, font.face()));
         }
+    }
+    let face =
+        |font_id: AssetId<TextMeshFont>| &faces.iter().find(|(id, _)| *id == font_id).unwrap().1;
 
-        let key = CacheKey::new_3d(char, depth);
+    // sections are split into runs of the first font having the glyphs, the
+    // section font or one of the fallback fonts
+    let runs = sections
+        .iter()
+        .zip(&section_fonts)
+        .enumerate()
+        .flat_map(|(index, (section, (font, synthesized)))| {
+            let font_style = section.style.font_style.as_ref();
+            let font_style = font_style.unwrap_or(&style.font_style);
 
-        let mesh = match cache.meshes.get(&key) {
-            Some(mesh) => mesh,
-            None => {
-                let glyph = font.glyph_from_char(char);
+            // fallback fonts are not faces of the family, hence synthesized
+            let fallback_style = font_style
+                .clone()
+                .intersection(FontStyle::BOLD | FontStyle::ITALIC);
+            let fonts = std::iter::once((font.id(), synthesized.clone()))
+                .chain(
+                    style
+                        .fallback_fonts
+                        .iter()
+                        .map(|font| (font.id(), fallback_style.clone())),
+                )
+                .collect::<Vec<_>>();
 
-                let mut glyph = match glyph {
-                    Ok(glyph) => glyph,
-                    Err(_) => {
-                        println!("Glyph {} not found", char);
-                        font.glyph_from_char('?').unwrap()
-                    }
-                };
+            let covering = |char: char| {
+                fonts
+                    .iter()
+                    .position(|(font_id, _)| face(*font_id).glyph_index(char).is_some())
+            };
 
-                let mesh = match &text_mesh.size.depth {
-                    Some(unit) => glyph
-                        .to_3d_mesh(text_mesh.style.mesh_quality, unit.as_scalar().unwrap())
-                        .unwrap(),
-                    None => todo!("2d glyphs are not implemented yet. Define depth"),
+            let mut runs: Vec<(usize, String)> = Vec::new();
+            for char in apply_case(&section.value, font_style).chars() {
+                let (font, char) = match covering(char) {
+                    // whitespace continues the current run
+                    _ if char.is_whitespace() => (runs.last().map_or(0, |(font, _)| *font), char),
+                    Some(font) => (font, char),
+                    None => {
+                        if cache.first_warning(Warning::MissingChar(font.id(), char)) {
+                            warn!(
+                                "glyph {:?} not found, replaced with {:?}",
+                                char, style.replacement_char
+                            );
+                        }
+                        // without a replacement glyph either, the .notdef glyph of the font is shown
+                        (
+                            covering(style.replacement_char).unwrap_or(0),
+                            style.replacement_char,
+                        )
+                    }
                 };
 
-                cache.meshes.insert(key.clone(), mesh);
-                cache.meshes.get(&key).unwrap()
+                match runs.last_mut() {
+                    Some((run_font, text)) if *run_font == font => text.push(char),
+                    _ => runs.push((font, char.to_string())),
+                }
             }
-        };
 
-        let (mut xmin, mut xmax) = (f32::MAX, f32::MIN);
-        let (mut ymin, mut ymax) = (f32::MAX, f32::MIN);
-        for vertex in mesh.iter_vertices() {
-            let (x, y, _z) = vertex.val();
-            // optimization possibility: calculate per-glyph min/max when caching
-            if x < xmin {
-                xmin = x;
-            }
-            if x > xmax {
-                xmax = x;
+            // empty sections still define the height of empty lines
+            if runs.is_empty() {
+                runs.push((0, String::new()));
             }
 
-            if y < ymin {
-                ymin = y;
-            }
-            if y > ymax {
-                ymax = y;
+            runs.into_iter().map(move |(font, text)| {
+                let (font_id, synthesized) = fonts[font].clone();
+                LayoutRun {
+                    section: index,
+                    font_id,
+                    synthesized,
+                    text,
+                    font_style: font_style.clone(),
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // synthetic emboldening and skew of the runs, in em units
+    let synthesis = runs
+        .iter()
+        .map(|run| {
+            let embolden = match run.synthesized.contains(FontStyle::BOLD) {
+                true => style.synthesis.embolden,
+                false => 0.,
+            };
+            let skew = match run.synthesized.contains(FontStyle::ITALIC) {
+                true => style.synthesis.skew,
+                false => 0.,
+            };
+            (embolden, skew)
+        })
+        .collect::<Vec<_>>();
+
+    let mut layout_sections = runs
+        .iter()
+        .zip(&synthesis)
+        .map(|(run, (embolden, _))| LayoutSection {
+            text: run.text.clone(),
+            face: face(run.font_id),
+            glyphs: Vec::new(),
+            font_size: sections[run.section].style.font_size.clone(),
+            underline: run.font_style.contains(FontStyle::UNDERLINE),
+            strikethrough: run.font_style.contains(FontStyle::STRIKETHROUGH),
+            embolden: *embolden,
+        })
+        .collect::<Vec<_>>();
+    shape_sections(&mut layout_sections, &style.direction, &style.writing_mode);
+
+    let scalar = match style.font_size.as_scalar(DEFAULT_FONT_SCALAR) {
+        Some(scalar) => scalar,
+        None => fit_font_size(text_mesh, &layout_sections),
+    };
+
+    // without depth, a flat single-sided mesh facing +z is generated
+    let depth = text_mesh
+        .size
+        .depth
+        .as_ref()
+        .map(|unit| match unit.as_scalar(scalar) {
+            Some(depth) => depth,
+            None => {
+                if cache.first_warning(Warning::AutoDepth) {
+                    warn!("automatic text mesh depth is not supported, using 0.05em");
+                }
+                0.05 * scalar
             }
+        });
+
+    let layout = layout_text(text_mesh, &layout_sections, scalar);
+    let anchor = layout.anchor(&text_mesh.alignment.anchor);
+
+    let vertex_color = |run: usize, index: usize| {
+        let section_color = sections[runs[run].section].style.color;
+        let color = match &style.vertex_colors {
+            TextMeshColors::Chars(colors) => colors.get(index).copied().or(section_color),
+            _ => section_color,
+        };
+        color.unwrap_or(Color::WHITE).as_linear_rgba_f32()
+    };
+
+    cache.next_generation();
+
+    for glyph in layout.glyphs {
+        let font_id = runs[glyph.section].font_id;
+        let scalar = layout_sections[glyph.section].scalar(scalar);
+
+        let (embolden, skew) = synthesis[glyph.section];
+        let key = match depth {
+            Some(_) => CacheKey::new_3d(font_id, glyph.glyph_id, style.mesh_quality),
+            None => CacheKey::new_2d(font_id, glyph.glyph_id, style.mesh_quality),
         }
+        .with_synthesis(embolden, skew);
+
+        let mesh = cache.get_or_insert_with(key, || {
+            let font = fonts.get(font_id).unwrap();
+            let mut ttf_font = font.ttf_font.lock().unwrap();
 
-        let y_diff = (ymax - ymin) * scalar;
-        if scaled_row_y_max_height < y_diff {
-            scaled_row_y_max_height = y_diff;
+            // the glyph indices of ttf2mesh are the glyph ids of the font
+            let Ok(mut ttf_glyph) = ttf_font.glyph_by_index(glyph.glyph_id.0 as usize) else {
+                return GlyphMesh::default();
+            };
+
+            let mut mesh = match embolden != 0. {
+                // the outline is dilated before the tessellation
+                true => {
+                    let Some(mut outline) = GlyphOutline::new(&font.face(), glyph.glyph_id) else {
+                        return GlyphMesh::default();
+                    };
+                    outline.embolden(embolden, style.mesh_quality);
+                    outline.tessellate(style.mesh_quality, depth.is_some())
+                }
+                false => tessellate(&mut ttf_glyph, style.mesh_quality, depth.is_some()),
+            };
+            if skew != 0. {
+                skew_mesh(&mut mesh, skew);
+            }
+            mesh
+        });
+
+        // glyph outlines are in em units, relative to the glyph origin on the baseline,
+        // hence already include the left side bearing. Extruded glyphs are one unit deep
+        let color = vertex_color(glyph.section, glyph.index);
+        let scale = Vec3::new(scalar, scalar, depth.unwrap_or_default());
+        let range = mesh_data.append(mesh, scale, glyph.position, color);
+        if glyph.rotated {
+            mesh_data.rotate_clockwise(range.clone(), glyph.position);
         }
+        glyph_ranges.push(range);
+    }
 
-        for vertex in mesh.iter_vertices() {
-            let (x, y, z) = vertex.val();
-            vertices.push([
-                x * scalar + scaled_offset.x - xmin * scalar,
-                y * scalar + scaled_offset.y,
-                z * scalar,
-            ]);
+    // underline and strikethrough bars are in world units
+    for decoration in layout.decorations {
+        let mesh = bar_mesh(decoration.size, depth);
+        let color = vertex_color(decoration.section, decoration.index);
+        glyph_ranges.push(mesh_data.append(&mesh, Vec3::ONE, decoration.position, color));
+    }
+
+    // the anchor of the laid out box is moved to the origin
+    mesh_data.translate(-anchor);
+
+    cache.evict();
+
+    match style.vertex_colors {
+        TextMeshColors::HorizontalGradient(start, end) => {
+            apply_gradient(&mesh_data.vertices, &mut mesh_data.colors, 0, start, end)
+        }
+        TextMeshColors::VerticalGradient(start, end) => {
+            apply_gradient(&mesh_data.vertices, &mut mesh_data.colors, 1, end, start)
         }
+        _ => (),
+    }
+
+    mesh_data.uvs = generate_uvs(
+        text_mesh,
+        &mesh_data.vertices,
+        &mesh_data.normals,
+        &glyph_ranges,
+    );
+
+    mesh_data
+}
+
+/// Shears the glyph horizontally, by `skew` x units per y unit
+fn skew_mesh(mesh: &mut GlyphMesh, skew: f32) {
+    for vertex in &mut mesh.vertices {
+        vertex[0] += vertex[1] * skew;
+    }
+
+    // normals transform by the inverse transpose of the shear
+    for normal in &mut mesh.normals {
+        let sheared = Vec3::new(normal[0], normal[1] - skew * normal[0], normal[2]);
+        *normal = sheared.normalize().to_array();
+    }
+}
+
+/// Box of the given size, extending from the origin towards +x and -y, and
+/// centered on z like the glyphs. Without depth, a flat rectangle facing +z
+fn bar_mesh(size: Vec2, depth: Option<f32>) -> GlyphMesh {
+    let mut mesh = GlyphMesh::default();
 
-        /*
-        println!(
-            " - x({:.3} - {:.3})={:.3}, y({:.3} - {:.3})={:.3}",
-            xmin * scalar,
-            xmax * scalar,
-            (xmax - xmin) * scalar,
-            ymin * scalar,
-            ymax * scalar,
-            (ymax - ymin) * scalar
+    let mut quad = |corners: [Vec3; 4], normal: Vec3| {
+        let offset = mesh.vertices.len() as u32;
+
+        mesh.vertices
+            .extend(corners.map(|corner| corner.to_array()));
+        mesh.normals.extend([normal.to_array(); 4]);
+        mesh.indices
+            .extend([0, 1, 2, 0, 2, 3].map(|index| index + offset));
+    };
+
+    let (x, y) = (size.x, -size.y);
+
+    let Some(depth) = depth else {
+        quad(
+            [
+                Vec3::new(0., y, 0.),
+                Vec3::new(x, y, 0.),
+                Vec3::new(x, 0., 0.),
+                Vec3::new(0., 0., 0.),
+            ],
+            Vec3::Z,
         );
-        */
-        // 13 microsecs
+        return mesh;
+    };
 
-        for normal in mesh.iter_normals().unwrap() {
-            let (x, y, z) = normal.val();
-            normals.push([x, y, z]);
-        }
-        // total = 24ms
-
-        for face in mesh.iter_faces() {
-            let val = face.val();
-            indices.extend_from_slice(&[
-                (val.0) as u32 + vertices_offset as u32,
-                (val.1) as u32 + vertices_offset as u32,
-                (val.2) as u32 + vertices_offset as u32,
-            ]);
+    let (back, front) = (-depth / 2., depth / 2.);
+
+    // counter-clockwise, when viewed from outside of the box
+    quad(
+        [
+            Vec3::new(0., y, front),
+            Vec3::new(x, y, front),
+            Vec3::new(x, 0., front),
+            Vec3::new(0., 0., front),
+        ],
+        Vec3::Z,
+    );
+    quad(
+        [
+            Vec3::new(x, y, back),
+            Vec3::new(0., y, back),
+            Vec3::new(0., 0., back),
+            Vec3::new(x, 0., back),
+        ],
+        Vec3::NEG_Z,
+    );
+    quad(
+        [
+            Vec3::new(0., 0., front),
+            Vec3::new(x, 0., front),
+            Vec3::new(x, 0., back),
+            Vec3::new(0., 0., back),
+        ],
+        Vec3::Y,
+    );
+    quad(
+        [
+            Vec3::new(0., y, back),
+            Vec3::new(x, y, back),
+            Vec3::new(x, y, front),
+            Vec3::new(0., y, front),
+        ],
+        Vec3::NEG_Y,
+    );
+    quad(
+        [
+            Vec3::new(x, y, front),
+            Vec3::new(x, y, back),
+            Vec3::new(x, 0., back),
+            Vec3::new(x, 0., front),
+        ],
+        Vec3::X,
+    );
+    quad(
+        [
+            Vec3::new(0., y, back),
+            Vec3::new(0., y, front),
+            Vec3::new(0., 0., front),
+            Vec3::new(0., 0., back),
+        ],
+        Vec3::NEG_X,
+    );
+
+    mesh
+}
+
+/// Texture coordinates by planar projection: front and back faces are
+/// projected along z, side walls along the outline and the depth
+fn generate_uvs(
+    text_mesh: &TextMesh,
+    vertices: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    glyph_ranges: &[Range<usize>],
+) -> Vec<[f32; 2]> {
+    let bounds = |vertices: &[[f32; 3]]| {
+        vertices.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), vertex| {
+                let vertex = Vec3::from(*vertex);
+                (min.min(vertex), max.max(vertex))
+            },
+        )
+    };
+    let normalize = |value: f32, min: f32, max: f32| {
+        if max > min {
+            (value - min) / (max - min)
+        } else {
+            0.
         }
-        // 30 microsecs
+    };
+
+    let style = &text_mesh.style;
+    let (block_min, block_max) = bounds(vertices);
+    let mut uvs = vec![[0., 0.]; vertices.len()];
 
-        vertices_offset += mesh.vertices_len();
+    for range in glyph_ranges {
+        let (min, max) = match style.uv_mode {
+            TextMeshUvMode::Block => (block_min, block_max),
+            TextMeshUvMode::Glyph => bounds(&vertices[range.clone()]),
+        };
 
-        scaled_offset.x += (xmax - xmin) * scalar + spacing.x;
+        for i in range.clone() {
+            let [x, y, z] = vertices[i];
+            let normal = normals[i];
 
-        if text_mesh.size.wrapping
-            && scaled_offset.x + scalar + spacing.x > text_mesh.size.width.as_scalar().unwrap()
-        {
-            scaled_offset.x = 0.;
-            scaled_offset.y -= scaled_row_y_max_height + spacing.y;
+            let u = normalize(x, min.x, max.x);
+            let v = normalize(max.y - y, 0., max.y - min.y);
+
+            uvs[i] = if normal[2].abs() > 0.5 {
+                match style.uv_side_walls {
+                    true => [u, v / 2.],
+                    false => [u, v],
+                }
+            } else {
+                // walls facing sideways run along the y axis
+                let u = match normal[0].abs() > normal[1].abs() {
+                    true => v,
+                    false => u,
+                };
+                let v = normalize(z, block_min.z, block_max.z);
+
+                match style.uv_side_walls {
+                    true => [u, 0.5 + v / 2.],
+                    false => [u, v],
+                }
+            };
         }
+    }
+
+    uvs
+}
+
+/// Interpolates the colors over the extent of the vertices along an axis
+fn apply_gradient(
+    vertices: &[[f32; 3]],
+    colors: &mut [[f32; 4]],
+    axis: usize,
+    start: Color,
+    end: Color,
+) {
+    let (min, max) = vertices
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), vertex| {
+            (min.min(vertex[axis]), max.max(vertex[axis]))
+        });
+
+    let start = Vec4::from(start.as_linear_rgba_f32());
+    let end = Vec4::from(end.as_linear_rgba_f32());
+
+    for (vertex, color) in vertices.iter().zip(colors) {
+        let t = if max > min {
+            (vertex[axis] - min) / (max - min)
+        } else {
+            0.
+        };
+        *color = start.lerp(end, t).to_array();
+    }
+}
 
-        //println!("");
+fn apply_case(text: &str, font_style: &FontStyle) -> String {
+    if font_style.contains(FontStyle::UPPERCASE) {
+        text.to_uppercase()
+    } else if font_style.contains(FontStyle::LOWERCASE) {
+        text.to_lowercase()
+    } else {
+        text.to_string()
     }
+}
+
+/// Tessellates a glyph into a 3d mesh of unit depth, or into a flat mesh facing
+/// +z. The extrusion is linear in the depth, so the 3d mesh is scaled to the
+/// depth afterwards. Glyphs without an outline produce an empty mesh
+fn tessellate(glyph: &mut ttf2mesh::Glyph, quality: Quality, extruded: bool) -> GlyphMesh {
+    let mut glyph_mesh = GlyphMesh::default();
 
-    let uvs = vertices.iter().map(|_vert| [0., 1.]).collect::<Vec<_>>();
+    let faces = match extruded {
+        true => {
+            let Ok(mesh) = glyph.to_3d_mesh(quality, 1.) else {
+                return glyph_mesh;
+            };
 
-    MeshData {
-        vertices,
-        normals,
-        indices,
-        uvs,
+            for vertex in mesh.iter_vertices() {
+                let (x, y, z) = vertex.val();
+                glyph_mesh.vertices.push([x, y, z]);
+            }
+            for normal in mesh.iter_normals().unwrap() {
+                let (x, y, z) = normal.val();
+                glyph_mesh.normals.push([x, y, z]);
+            }
+            mesh.iter_faces().map(|face| face.val()).collect::<Vec<_>>()
+        }
+        false => {
+            let Ok(mesh) = glyph.to_2d_mesh(quality) else {
+                return glyph_mesh;
+            };
+
+            for vertex in mesh.iter_vertices() {
+                let (x, y) = vertex.val();
+                glyph_mesh.vertices.push([x, y, 0.]);
+                glyph_mesh.normals.push([0., 0., 1.]);
+            }
+            mesh.iter_faces().map(|face| face.val()).collect::<Vec<_>>()
+        }
+    };
+
+    for (a, b, c) in faces {
+        glyph_mesh
+            .indices
+            .extend_from_slice(&[a as u32, b as u32, c as u32]);
     }
+
+    glyph_mesh
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use crate::{
-        mesh_data_generator::generate_text_mesh, text_mesh::TextMesh, SizeUnit, TextMeshSize,
-        TextMeshStyle,
+        mesh_data_generator::generate_text_mesh, text_mesh::TextMesh, HorizontalAlign, SizeUnit,
+        TextMeshAlignment, TextMeshAnchor, TextMeshFontFamily, TextMeshSize, TextMeshStyle,
+        TextMeshWritingMode, VerticalAlign,
     };
 
     use super::*;
@@ -201,10 +631,20 @@ mod tests {
         std::fs::read("./assets/fonts/FiraMono-Medium.ttf").unwrap()
     }
 
+    /// Font assets, with the font behind the default handle
+    pub(crate) fn get_fonts() -> Assets<TextMeshFont> {
+        let mut fonts = Assets::default();
+        fonts.insert(
+            AssetId::default(),
+            TextMeshFont::from_bytes(get_font_bytes()).unwrap(),
+        );
+        fonts
+    }
+
     #[test]
     fn test_generate_mesh() {
         let mut mesh_cache = MeshCache::default();
-        let mut font = ttf2mesh::TTFFile::from_buffer_vec(get_font_bytes()).unwrap();
+        let fonts = get_fonts();
 
         let text_mesh = TextMesh {
             text: "hello world!".to_string(),
@@ -220,7 +660,600 @@ mod tests {
             ..Default::default()
         };
 
-        let _ = generate_text_mesh(&text_mesh, &mut font, Some(&mut mesh_cache));
+        let _ = generate_text_mesh(&text_mesh, &fonts, Some(&mut mesh_cache));
+    }
+
+    fn mesh_bounds(mesh: &MeshData) -> (Vec2, Vec2) {
+        mesh.vertices.iter().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), v| {
+                (
+                    min.min(Vec2::new(v[0], v[1])),
+                    max.max(Vec2::new(v[0], v[1])),
+                )
+            },
+        )
+    }
+
+    #[test]
+    fn test_alignment() {
+        let fonts = get_fonts();
+
+        let mut text_mesh = TextMesh {
+            text: "hello".to_string(),
+            size: TextMeshSize {
+                width: SizeUnit::NonStandard(72. * 8.),
+                height: SizeUnit::NonStandard(72. * 2.),
+                ..Default::default()
+            },
+            style: TextMeshStyle {
+                font_size: SizeUnit::NonStandard(72.),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // "hello" is five 0.6em advances wide in the monospace font,
+        // and the line box is 1.2em high (ascender 0.935em, descender -0.265em)
+        let (min, max) = mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+        assert!(min.x > 0. && min.x < 0.1);
+        assert!(max.y < 0. && min.y > -1.2);
+
+        text_mesh.alignment = TextMeshAlignment {
+            horizontal: HorizontalAlign::Right,
+            vertical: VerticalAlign::Bottom,
+            ..Default::default()
+        };
+        let (min, max) = mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+        assert!(max.x < 8. && max.x > 7.9);
+        assert!(min.y > -2. && max.y < -0.8);
+
+        text_mesh.alignment = TextMeshAlignment {
+            horizontal: HorizontalAlign::Center,
+            vertical: VerticalAlign::Center,
+            ..Default::default()
+        };
+        let (min, max) = mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+        assert!(((min.x + max.x) / 2. - 4.).abs() < 0.05);
+        assert!(min.y > -1.6 && max.y < -0.4);
+    }
+
+    #[test]
+    fn test_anchor() {
+        let fonts = get_fonts();
+
+        // the box shrinks to "hello": five 0.6em advances, and a 1.2em line
+        let mut text_mesh = TextMesh {
+            text: "hello".to_string(),
+            size: TextMeshSize {
+                width: SizeUnit::Auto,
+                height: SizeUnit::Auto,
+                ..Default::default()
+            },
+            style: TextMeshStyle {
+                font_size: SizeUnit::NonStandard(72.),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (min, max) = mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+
+        let offsets = [
+            (TextMeshAnchor::Center, Vec2::new(-1.5, 0.6)),
+            (TextMeshAnchor::BottomRight, Vec2::new(-3., 1.2)),
+            (TextMeshAnchor::BaselineLeft, Vec2::new(0., 0.935)),
+            (
+                TextMeshAnchor::Custom(Vec2::new(0., 0.5)),
+                Vec2::new(-1.5, 0.),
+            ),
+        ];
+        for (anchor, offset) in offsets {
+            text_mesh.alignment.anchor = anchor;
+            let (anchored_min, anchored_max) =
+                mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+
+            assert!((anchored_min - min).distance(offset) < 1e-4);
+            assert!((anchored_max - max).distance(offset) < 1e-4);
+        }
+
+        // the baseline of an em wide column (without a vhea table) is at its center
+        text_mesh.style.writing_mode = TextMeshWritingMode::Vertical;
+        text_mesh.alignment.anchor = TextMeshAnchor::TopLeft;
+        let (min, max) = mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+
+        text_mesh.alignment.anchor = TextMeshAnchor::BaselineLeft;
+        let (anchored_min, anchored_max) =
+            mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+        assert!((anchored_min - min).distance(Vec2::new(-0.5, 0.)) < 1e-4);
+        assert!((anchored_max - max).distance(Vec2::new(-0.5, 0.)) < 1e-4);
+    }
+
+    #[test]
+    fn test_size_units() {
+        let fonts = get_fonts();
+
+        let mut text_mesh = TextMesh {
+            text: "m".to_string(),
+            size: TextMeshSize {
+                depth: Some(SizeUnit::Cm(10.)),
+                ..Default::default()
+            },
+            style: TextMeshStyle {
+                font_size: SizeUnit::World(0.5),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mesh = generate_text_mesh(&text_mesh, &fonts, None);
+        let (min, max) = mesh_bounds(&mesh);
+
+        // "m" is 0.542em tall, the depth is in world units regardless of the font size
+        assert!((max.y - min.y - 0.271).abs() < 1e-4);
+        let (zmin, zmax) = mesh
+            .vertices
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), v| {
+                (min.min(v[2]), max.max(v[2]))
+            });
+        assert!((zmax - zmin - 0.1).abs() < 1e-4);
+
+        // automatic depth falls back to 0.05em
+        text_mesh.size.depth = Some(SizeUnit::Auto);
+        let (zmin, zmax) = generate_text_mesh(&text_mesh, &fonts, None)
+            .vertices
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), v| {
+                (min.min(v[2]), max.max(v[2]))
+            });
+        assert!((zmax - zmin - 0.025).abs() < 1e-4);
+
+        text_mesh.size.depth = Some(SizeUnit::Cm(10.));
+        text_mesh.style.font_size = SizeUnit::Pt(72.);
+        let (min, max) = mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+        assert!((max.y - min.y - 0.542 * 0.0254).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_line_metrics() {
+        let fonts = get_fonts();
+
+        let mut text_mesh = TextMesh {
+            text: "m".to_string(),
+            style: TextMeshStyle {
+                font_size: SizeUnit::NonStandard(72.),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // "m" sits on the baseline, which is one ascender below the origin
+        let (min, _) = mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+        assert!((min.y + 0.935).abs() < 1e-4);
+
+        // glyphs share the baseline instead of their bottom edges
+        text_mesh.text = "gm".to_string();
+        let (min, _) = mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+        assert!((min.y + 0.935 + 0.216).abs() < 1e-4);
+
+        // lines are separated by ascender - descender + line gap
+        text_mesh.text = "m\nm".to_string();
+        let (min, _) = mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+        assert!((min.y + 0.935 + 1.2).abs() < 1e-4);
+
+        text_mesh.style.line_height = 1.5;
+        let (min, _) = mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+        assert!((min.y + 0.935 + 1.8).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_flat_mesh() {
+        let mut mesh_cache = MeshCache::default();
+        let fonts = get_fonts();
+
+        let text_mesh = TextMesh {
+            text: "hello world!".to_string(),
+            size: TextMeshSize {
+                depth: None,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mesh = generate_text_mesh(&text_mesh, &fonts, Some(&mut mesh_cache));
+        assert!(!mesh.indices.is_empty());
+        assert!(mesh.vertices.iter().all(|v| v[2] == 0.));
+        assert!(mesh.normals.iter().all(|n| *n == [0., 0., 1.]));
+
+        // front faces are counter-clockwise when viewed from +z
+        for triangle in mesh.indices.chunks(3) {
+            let [a, b, c] =
+                [0, 1, 2].map(|i| Vec2::from_slice(&mesh.vertices[triangle[i] as usize]));
+            assert!((b - a).perp_dot(c - a) >= 0.);
+        }
+
+        // flat glyphs are cached separately from 3d glyphs
+        let _ = generate_text_mesh(&TextMesh::new_no_font("h"), &fonts, Some(&mut mesh_cache));
+        let glyph_id = fonts
+            .get(AssetId::default())
+            .unwrap()
+            .face()
+            .glyph_index('h');
+        assert!(mesh_cache.meshes.contains_key(&CacheKey::new_2d(
+            AssetId::default(),
+            glyph_id.unwrap(),
+            Quality::Medium
+        )));
+        assert_eq!(mesh_cache.meshes.len(), 9);
+    }
+
+    #[test]
+    fn test_cache_keys() {
+        let mut mesh_cache = MeshCache::default();
+        let mut fonts = get_fonts();
+        let other_font = fonts.add(TextMeshFont::from_bytes(get_font_bytes()).unwrap());
+
+        let mut text_mesh = TextMesh::new_no_font("l");
+        let _ = generate_text_mesh(&text_mesh, &fonts, Some(&mut mesh_cache));
+        let _ = generate_text_mesh(&text_mesh, &fonts, Some(&mut mesh_cache));
+        assert_eq!(mesh_cache.meshes.len(), 1);
+
+        // glyphs of different fonts and qualities do not share cache entries
+        text_mesh.style.mesh_quality = Quality::High;
+        let _ = generate_text_mesh(&text_mesh, &fonts, Some(&mut mesh_cache));
+        assert_eq!(mesh_cache.meshes.len(), 2);
+
+        text_mesh.style.font = other_font;
+        let _ = generate_text_mesh(&text_mesh, &fonts, Some(&mut mesh_cache));
+        assert_eq!(mesh_cache.meshes.len(), 3);
+
+        // extruded glyphs are shared by all depths and font sizes
+        text_mesh.size.depth = Some(SizeUnit::NonStandard(1.));
+        text_mesh.style.font_size = SizeUnit::NonStandard(11.);
+        let _ = generate_text_mesh(&text_mesh, &fonts, Some(&mut mesh_cache));
+        assert_eq!(mesh_cache.meshes.len(), 3);
+
+        text_mesh.size.depth = None;
+        let _ = generate_text_mesh(&text_mesh, &fonts, Some(&mut mesh_cache));
+        assert_eq!(mesh_cache.meshes.len(), 4);
+    }
+
+    #[test]
+    fn test_cache_eviction() {
+        let mut mesh_cache = MeshCache::default();
+        mesh_cache.max_glyphs = Some(3);
+        let fonts = get_fonts();
+
+        // glyphs of the current text are kept, even if over the budget
+        let _ = generate_text_mesh(
+            &TextMesh::new_no_font("abcd"),
+            &fonts,
+            Some(&mut mesh_cache),
+        );
+        assert_eq!(mesh_cache.len(), 4);
+
+        // least recently used glyphs are evicted first
+        let _ = generate_text_mesh(&TextMesh::new_no_font("ae"), &fonts, Some(&mut mesh_cache));
+        assert_eq!(mesh_cache.len(), 3);
+        let cached = |cache: &MeshCache, char| {
+            let glyph_id = fonts
+                .get(AssetId::default())
+                .unwrap()
+                .face()
+                .glyph_index(char);
+            cache
+                .meshes
+                .keys()
+                .any(|key| Some(key.glyph_id) == glyph_id)
+        };
+        assert!(cached(&mesh_cache, 'a') && cached(&mesh_cache, 'e'));
+
+        let bytes = mesh_cache.size_in_bytes();
+        mesh_cache.max_glyphs = None;
+        mesh_cache.max_bytes = Some(bytes - 1);
+        let _ = generate_text_mesh(&TextMesh::new_no_font("a"), &fonts, Some(&mut mesh_cache));
+        assert!(mesh_cache.len() < 3 && mesh_cache.size_in_bytes() < bytes);
+
+        mesh_cache.clear_font(Handle::<TextMeshFont>::weak_from_u128(1));
+        assert!(!mesh_cache.is_empty());
+        mesh_cache.clear_font(AssetId::default());
+        assert!(mesh_cache.is_empty());
+        assert_eq!(mesh_cache.size_in_bytes(), 0);
+    }
+
+    #[test]
+    fn test_sections() {
+        let fonts = get_fonts();
+
+        let mut text_mesh = TextMesh::from_sections(
+            [
+                TextMeshSection::new("m"),
+                TextMeshSection::new("m").with_color(Color::RED),
+            ],
+            Handle::default(),
+        );
+        text_mesh.style.font_size = SizeUnit::NonStandard(72.);
+
+        // sections are laid out on a shared line, colored by vertex colors
+        let mesh = generate_text_mesh(&text_mesh, &fonts, None);
+        let (min, max) = mesh_bounds(&mesh);
+        assert!((min.y + 0.935).abs() < 1e-4);
+        assert!((max.x - min.x - 1.2).abs() < 0.1);
+
+        let white = Color::WHITE.as_linear_rgba_f32();
+        let red = Color::RED.as_linear_rgba_f32();
+        assert_eq!(mesh.colors.len(), mesh.vertices.len());
+        assert_eq!(mesh.colors[0], white);
+        assert_eq!(mesh.colors[mesh.colors.len() - 1], red);
+
+        // larger sections push the shared baseline down
+        text_mesh.sections[1].style.font_size = Some(SizeUnit::Em(2.));
+        let (min, max) = mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+        assert!((min.y + 0.935 * 2.).abs() < 1e-4);
+        assert!((max.y - min.y - 0.542 * 2.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_vertex_colors() {
+        let fonts = get_fonts();
+        let red = Color::RED.as_linear_rgba_f32();
+        let blue = Color::BLUE.as_linear_rgba_f32();
+
+        let mut text_mesh = TextMesh::new_no_font("m m");
+        text_mesh.style.vertex_colors =
+            TextMeshColors::Chars(vec![Color::RED, Color::GREEN, Color::BLUE]);
+
+        // whitespace takes up a color, too
+        let mesh = generate_text_mesh(&text_mesh, &fonts, None);
+        assert_eq!(mesh.colors[0], red);
+        assert_eq!(mesh.colors[mesh.colors.len() - 1], blue);
+
+        text_mesh.style.vertex_colors = TextMeshColors::HorizontalGradient(Color::RED, Color::BLUE);
+        let mesh = generate_text_mesh(&text_mesh, &fonts, None);
+        let (min, max) = mesh_bounds(&mesh);
+
+        for (vertex, color) in mesh.vertices.iter().zip(&mesh.colors) {
+            if vertex[0] == min.x {
+                assert_eq!(*color, red);
+            } else if vertex[0] == max.x {
+                assert_eq!(*color, blue);
+            } else {
+                assert!(color[0] < 1. && color[2] < 1.);
+            }
+        }
+    }
+
+    #[test]
+    fn test_uvs() {
+        let fonts = get_fonts();
+
+        let mut text_mesh = TextMesh::new_no_font("mm");
+        let in_unit_square =
+            |uv: &[f32; 2]| (0.0..=1.0).contains(&uv[0]) && (0.0..=1.0).contains(&uv[1]);
+
+        // the block spans the texture once, v growing downwards
+        let mesh = generate_text_mesh(&text_mesh, &fonts, None);
+        assert!(mesh.uvs.iter().all(in_unit_square));
+        let (min, _) = mesh_bounds(&mesh);
+        let left = mesh.vertices.iter().position(|v| v[0] == min.x).unwrap();
+        assert_eq!(mesh.uvs[left][0], 0.);
+        let bottom = mesh.vertices.iter().position(|v| v[1] == min.y).unwrap();
+        assert_eq!(mesh.uvs[bottom][1], 1.);
+
+        // each glyph spans the texture
+        text_mesh.style.uv_mode = TextMeshUvMode::Glyph;
+        let mesh = generate_text_mesh(&text_mesh, &fonts, None);
+        let half = mesh.vertices.len() / 2;
+        let max_u = |uvs: &[[f32; 2]]| uvs.iter().map(|uv| uv[0]).fold(0., f32::max);
+        assert_eq!(max_u(&mesh.uvs[..half]), 1.);
+        assert_eq!(max_u(&mesh.uvs[half..]), 1.);
+
+        // side walls are in the lower half of the texture
+        text_mesh.style.uv_side_walls = true;
+        let mesh = generate_text_mesh(&text_mesh, &fonts, None);
+        for (normal, uv) in mesh.normals.iter().zip(&mesh.uvs) {
+            assert!(in_unit_square(uv));
+            match normal[2].abs() > 0.5 {
+                true => assert!(uv[1] <= 0.5),
+                false => assert!(uv[1] >= 0.5),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decorations() {
+        let fonts = get_fonts();
+
+        let mut text_mesh = TextMesh {
+            text: "ab cd ".to_string(),
+            size: TextMeshSize {
+                width: SizeUnit::NonStandard(72. * 2.),
+                ..Default::default()
+            },
+            style: TextMeshStyle {
+                font_size: SizeUnit::NonStandard(72.),
+                font_style: FontStyle::UNDERLINE,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let plain = |text_mesh: &TextMesh| {
+            let mut text_mesh = text_mesh.clone();
+            text_mesh.style.font_style = FontStyle::empty();
+            generate_text_mesh(&text_mesh, &fonts, None)
+        };
+
+        // one bar per wrapped line, spanning the glyphs without trailing whitespace:
+        // underline at -0.075em, 0.05em thick, below the baseline at -0.935em
+        let mesh = generate_text_mesh(&text_mesh, &fonts, None);
+        let bars = &mesh.vertices[plain(&text_mesh).vertices.len()..];
+        assert_eq!(bars.len(), 2 * 24);
+
+        let (min, max) = mesh_bounds(&MeshData {
+            vertices: bars[..24].to_vec(),
+            ..Default::default()
+        });
+        assert!(min.x.abs() < 1e-4 && (max.x - 1.2).abs() < 1e-4);
+        assert!((max.y + 0.935 + 0.075).abs() < 1e-4);
+        assert!((max.y - min.y - 0.05).abs() < 1e-4);
+
+        // bar faces are wound counter-clockwise around their normals
+        for triangle in mesh.indices.chunks(3).rev().take(2 * 12) {
+            let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(mesh.vertices[triangle[i] as usize]));
+            let normal = Vec3::from(mesh.normals[triangle[0] as usize]);
+            assert!((b - a).cross(c - a).dot(normal) > 0.);
+        }
+
+        // flat bars, and both decorations together
+        text_mesh.size.depth = None;
+        text_mesh.style.font_style = FontStyle::UNDERLINE | FontStyle::STRIKETHROUGH;
+        let mesh = generate_text_mesh(&text_mesh, &fonts, None);
+        assert_eq!(
+            mesh.vertices.len() - plain(&text_mesh).vertices.len(),
+            4 * 4
+        );
+    }
+
+    #[test]
+    fn test_synthesis() {
+        let fonts = get_fonts();
+
+        let mut text_mesh = TextMesh::new_no_font("ll");
+        text_mesh.style.font_size = SizeUnit::NonStandard(72.);
+        let (min, max) = mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+
+        // bold glyphs are dilated, and advance further
+        text_mesh.style.font_style = FontStyle::BOLD;
+        let (bold_min, bold_max) = mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+        assert!((bold_min.x - min.x).abs() < 1e-4);
+        // two shifts and the dilation of the second glyph, corners extend further
+        let widening = bold_max.x - max.x;
+        assert!(widening > 4. * 0.02 - 1e-4 && widening < 5. * 0.02);
+        assert!((max.y - bold_max.y + 0.02).abs() < 1e-3);
+
+        // heavily dilated glyphs merge their contours, and keep facing the front
+        let mut heavy = text_mesh.clone();
+        heavy.style.synthesis.embolden = 0.05;
+        for char in "dgmuB3@&$".chars() {
+            heavy.text = char.to_string();
+            let mesh = generate_text_mesh(&heavy, &fonts, None);
+            assert!(!mesh.indices.is_empty());
+
+            for triangle in mesh.indices.chunks(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(mesh.vertices[triangle[i] as usize]));
+                let normal = Vec3::from(mesh.normals[triangle[0] as usize]);
+                if normal.z.abs() > 0.5 {
+                    assert!((b - a).cross(c - a).dot(normal) >= 0.);
+                }
+            }
+        }
+
+        // italic glyphs lean right, by the skew times the height above the baseline
+        text_mesh.style.font_style = FontStyle::empty();
+        let plain = generate_text_mesh(&text_mesh, &fonts, None);
+        text_mesh.style.font_style = FontStyle::ITALIC;
+        let mesh = generate_text_mesh(&text_mesh, &fonts, None);
+
+        for (plain, italic) in plain.vertices.iter().zip(&mesh.vertices) {
+            assert!((italic[0] - plain[0] - 0.2 * (plain[1] + 0.935)).abs() < 1e-4);
+            assert_eq!(italic[1], plain[1]);
+        }
+        for normal in &mesh.normals {
+            assert!((Vec3::from(*normal).length() - 1.).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_font_family() {
+        let mut mesh_cache = MeshCache::default();
+        let mut fonts = get_fonts();
+        let bold = fonts.add(TextMeshFont::from_bytes(get_font_bytes()).unwrap());
+
+        let mut text_mesh = TextMesh::new_no_font("l");
+        text_mesh.style.font_size = SizeUnit::NonStandard(72.);
+        text_mesh.style.font_families =
+            vec![TextMeshFontFamily::new(Handle::default()).with_bold(bold.clone())];
+        let plain = generate_text_mesh(&text_mesh, &fonts, Some(&mut mesh_cache));
+
+        // the bold face is used as is, instead of synthesizing from the regular face
+        text_mesh.style.font_style = FontStyle::BOLD;
+        let mesh = generate_text_mesh(&text_mesh, &fonts, Some(&mut mesh_cache));
+        assert_eq!(mesh.vertices, plain.vertices);
+        assert!(mesh_cache.meshes.keys().any(|key| key.font == bold.id()));
+
+        // without an italic face, the italic is synthesized from the bold face
+        text_mesh.style.font_style = FontStyle::BOLD | FontStyle::ITALIC;
+        let mesh = generate_text_mesh(&text_mesh, &fonts, Some(&mut mesh_cache));
+        let (_, max) = mesh_bounds(&plain);
+        let (_, italic_max) = mesh_bounds(&mesh);
+        assert!(italic_max.x > max.x);
+        assert_eq!(mesh_cache.len(), 3);
+    }
+
+    #[test]
+    fn test_missing_glyphs() {
+        let fonts = get_fonts();
+
+        let mut text_mesh = TextMesh::new_no_font("漢");
+        text_mesh.style.font_size = SizeUnit::NonStandard(72.);
+
+        // FiraMono has no replacement char either, hence its .notdef box is shown
+        let (min, max) = mesh_bounds(&generate_text_mesh(&text_mesh, &fonts, None));
+        assert!((min - Vec2::new(0.09, -0.935 - 0.35)).abs().max_element() < 0.01);
+        assert!((max - Vec2::new(0.51, -0.935 + 1.05)).abs().max_element() < 0.01);
+
+        // missing glyphs are replaced with the replacement char
+        text_mesh.style.replacement_char = '□';
+        let replaced = generate_text_mesh(&text_mesh, &fonts, None);
+        let expected = generate_text_mesh(
+            &TextMesh {
+                text: "□".into(),
+                ..text_mesh.clone()
+            },
+            &fonts,
+            None,
+        );
+        assert_eq!(replaced.vertices, expected.vertices);
+
+        // ..and looked up from the fallback fonts first
+        let mut fonts = fonts;
+        text_mesh.style.fallback_fonts =
+            vec![fonts.add(TextMeshFont::from_bytes(get_font_bytes()).unwrap())];
+        let mesh = generate_text_mesh(&text_mesh, &fonts, None);
+        assert_eq!(mesh.vertices, expected.vertices);
+
+        // regenerated text meshes do not repeat the warning
+        let mut mesh_cache = MeshCache::default();
+        let _ = generate_text_mesh(&text_mesh, &fonts, Some(&mut mesh_cache));
+        let font = text_mesh.style.font.id();
+        assert!(!mesh_cache.first_warning(Warning::MissingChar(font, '漢')));
+        assert!(mesh_cache.first_warning(Warning::MissingChar(font, '字')));
+    }
+
+    #[test]
+    fn test_glyph_advance() {
+        let fonts = get_fonts();
+
+        let text_mesh = |text: &str| TextMesh {
+            text: text.to_string(),
+            style: TextMeshStyle {
+                font_size: SizeUnit::NonStandard(72.),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // the pen advances by the font's advance width (600 units of 1000 per em),
+        // regardless of the glyph outline width
+        let (_, l_max) = mesh_bounds(&generate_text_mesh(&text_mesh("l"), &fonts, None));
+        let (_, ml_max) = mesh_bounds(&generate_text_mesh(&text_mesh("ml"), &fonts, None));
+        let (_, il_max) = mesh_bounds(&generate_text_mesh(&text_mesh("il"), &fonts, None));
+        let (_, space_max) = mesh_bounds(&generate_text_mesh(&text_mesh(" l"), &fonts, None));
+
+        assert!((ml_max.x - l_max.x - 0.6).abs() < 1e-4);
+        assert!((il_max.x - l_max.x - 0.6).abs() < 1e-4);
+        assert!((space_max.x - l_max.x - 0.6).abs() < 1e-4);
     }
 }
 
@@ -234,23 +1267,23 @@ mod bench {
     #[bench]
     fn bench_get_glyph_cached(b: &mut Bencher) {
         let mut mesh_cache = MeshCache::default();
-        let mut font = ttf2mesh::TTFFile::from_buffer_vec(tests::get_font_bytes()).unwrap();
+        let fonts = tests::get_fonts();
 
         let text_mesh = TextMesh::new_no_font("hello world!".to_string());
-        let _ = generate_text_mesh(&text_mesh, &mut font, Some(&mut mesh_cache));
+        let _ = generate_text_mesh(&text_mesh, &fonts, Some(&mut mesh_cache));
 
         b.iter(|| {
-            let _ = generate_text_mesh(&text_mesh, &mut font, Some(&mut mesh_cache));
+            let _ = generate_text_mesh(&text_mesh, &fonts, Some(&mut mesh_cache));
         });
     }
 
     #[bench]
     fn bench_get_glyph_no_cache(b: &mut Bencher) {
-        let mut font = ttf2mesh::TTFFile::from_buffer_vec(tests::get_font_bytes()).unwrap();
+        let fonts = tests::get_fonts();
         let text_mesh = TextMesh::new_no_font("hello world!".to_string());
 
         b.iter(|| {
-            let _ = generate_text_mesh(&text_mesh, &mut font, None);
+            let _ = generate_text_mesh(&text_mesh, &fonts, None);
         });
     }
 }
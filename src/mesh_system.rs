@@ -1,16 +1,22 @@
 use bevy::render::render_resource::PrimitiveTopology;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
 use bevy::{prelude::*, render::mesh::Indices};
 
 use crate::{
     font_loader::TextMeshFont, mesh_cache::MeshCache, mesh_data_generator::generate_text_mesh,
 };
-use crate::{mesh_data_generator::MeshData, text_mesh::TextMesh};
+use crate::{
+    mesh_data_generator::MeshData,
+    text_mesh::{TextMesh, TextMesh2d},
+};
 
+#[allow(clippy::type_complexity)]
 pub(crate) fn text_mesh(
     mut commands: Commands,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut color_materials: Option<ResMut<Assets<ColorMaterial>>>,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut fonts: ResMut<Assets<TextMeshFont>>,
+    fonts: Res<Assets<TextMeshFont>>,
     mut text_meshes: Query<
         (
             Entity,
@@ -19,6 +25,9 @@ pub(crate) fn text_mesh(
             Option<&Handle<StandardMaterial>>,
             &TextMesh,
             Option<&Handle<Mesh>>,
+            Option<&Handle<ColorMaterial>>,
+            Option<&Mesh2dHandle>,
+            Has<TextMesh2d>,
             &mut TextMeshState,
         ),
         Or<(Changed<TextMesh>, Changed<TextMeshState>)>,
@@ -40,49 +49,110 @@ pub(crate) fn text_mesh(
     // TODO: performance - split to mesh-update and mesh-create systems?
 
     for text_mesh in text_meshes.iter_mut() {
-        let (entity, transform, global_transform, material, text_mesh, mesh, mut state) = text_mesh;
+        let (
+            entity,
+            transform,
+            global_transform,
+            material,
+            text_mesh,
+            mesh,
+            color_material,
+            mesh_2d,
+            is_2d,
+            mut state,
+        ) = text_mesh;
 
-        let font = match fonts.get_mut(&text_mesh.style.font) {
-            Some(font) => font,
-            None => {
-                if !state.warning_shown {
-                    state.warning_trigger_count += 1;
+        let mesh = mesh.or(mesh_2d.map(|mesh| &mesh.0));
 
-                    if state.warning_trigger_count > 5 {
-                        warn!("font mesh not found - did you load the font? (`asset_server.load('font.ttf#mesh'))`");
-                        state.warning_shown = true;
-                    }
+        // color is applied to the material only, and does not affect the geometry.
+        // User-supplied materials are never modified
+        if mesh.is_some() && state.material_owned {
+            let color = text_mesh.style.color;
+
+            if let Some(handle) = material {
+                if materials.get(handle).is_some_and(|m| m.base_color != color) {
+                    materials.get_mut(handle).unwrap().base_color = color;
                 }
-                continue;
             }
-        };
 
-        let ttf2_mesh = generate_text_mesh(&text_mesh, &mut font.ttf_font, Some(&mut cache));
+            if let (Some(handle), Some(color_materials)) =
+                (color_material, color_materials.as_mut())
+            {
+                if color_materials
+                    .get(handle)
+                    .is_some_and(|m| m.color != color)
+                {
+                    color_materials.get_mut(handle).unwrap().color = color;
+                }
+            }
+        }
+
+        let mut mesh_source = text_mesh.clone();
+        mesh_source.style.color = Color::default();
+
+        if mesh.is_some() && state.mesh_source.as_ref() == Some(&mesh_source) {
+            continue;
+        }
+
+        if !text_mesh.fonts().all(|font| fonts.contains(font)) {
+            if !state.warning_shown {
+                state.warning_trigger_count += 1;
+
+                if state.warning_trigger_count > 5 {
+                    warn!("font mesh not found - did you load the font? (`asset_server.load('font.ttf#mesh'))`");
+                    state.warning_shown = true;
+                }
+            }
+            continue;
+        }
+
+        let ttf2_mesh = generate_text_mesh(text_mesh, &fonts, Some(&mut cache));
+
+        // bookkeeping only, must not trigger the system again
+        let state = state.bypass_change_detection();
+        state.mesh_source = Some(mesh_source);
 
         match mesh {
             Some(mesh) => {
                 let mesh = meshes.get_mut(mesh).unwrap();
-                apply_mesh(ttf2_mesh, mesh);
-
-                // TODO: handle color updates
+                apply_mesh(ttf2_mesh, mesh, text_mesh.style.generate_tangents);
             }
             None => {
                 let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
 
-                apply_mesh(ttf2_mesh, &mut mesh);
-
-                commands.entity(entity).insert(PbrBundle {
-                    mesh: meshes.add(mesh),
-                    material: material.map(|m| m.clone()).unwrap_or_else(|| {
-                        materials.add(StandardMaterial {
-                            base_color: text_mesh.style.color,
-                            ..Default::default()
-                        })
-                    }),
-                    transform: transform.clone(),
-                    global_transform: global_transform.clone(),
-                    ..Default::default()
-                });
+                apply_mesh(ttf2_mesh, &mut mesh, text_mesh.style.generate_tangents);
+
+                if is_2d {
+                    let Some(color_materials) = color_materials.as_mut() else {
+                        warn!("ColorMaterial assets not found - 2d text meshes require the bevy_sprite plugin");
+                        continue;
+                    };
+
+                    state.material_owned = color_material.is_none();
+                    commands.entity(entity).insert(MaterialMesh2dBundle {
+                        mesh: meshes.add(mesh).into(),
+                        material: color_material.cloned().unwrap_or_else(|| {
+                            color_materials.add(ColorMaterial::from(text_mesh.style.color))
+                        }),
+                        transform: *transform,
+                        global_transform: *global_transform,
+                        ..Default::default()
+                    });
+                } else {
+                    state.material_owned = material.is_none();
+                    commands.entity(entity).insert(PbrBundle {
+                        mesh: meshes.add(mesh),
+                        material: material.cloned().unwrap_or_else(|| {
+                            materials.add(StandardMaterial {
+                                base_color: text_mesh.style.color,
+                                ..Default::default()
+                            })
+                        }),
+                        transform: *transform,
+                        global_transform: *global_transform,
+                        ..Default::default()
+                    });
+                }
             }
         }
     }
@@ -91,23 +161,24 @@ pub(crate) fn text_mesh(
 pub(crate) fn font_loaded(
     mut events: EventReader<AssetEvent<TextMeshFont>>,
     mut query: Query<(&mut TextMeshState, &TextMesh)>,
+    mut cache: ResMut<MeshCache>,
 ) {
-    // FIXME: this event system is triggered any time a new text is rendered
-    // by AssetEvent::Modified caused by font.get_mut(). Improve performance?
-
     for event in events.read() {
         match event {
             AssetEvent::LoadedWithDependencies { id } => {
                 for (mut state, text_mesh) in query.iter_mut() {
-                    if id == &text_mesh.style.font.id() {
+                    if text_mesh.fonts().any(|font| font.id() == *id) {
                         state.font_loaded = Some(true);
+                        state.mesh_source = None;
                     }
                 }
             }
             AssetEvent::Removed { id } => {
+                cache.clear_font(*id);
+
                 // why would this happen? handling anyway
                 for (mut state, text_mesh) in query.iter_mut() {
-                    if id == &text_mesh.style.font.id() {
+                    if text_mesh.fonts().any(|font| font.id() == *id) {
                         state.font_loaded = Some(false);
                     }
                 }
@@ -117,7 +188,7 @@ pub(crate) fn font_loaded(
     }
 }
 
-#[derive(Debug, Component)]
+#[derive(Debug, Default, Component)]
 pub struct TextMeshState {
     // this state matters only when the fonts have not been loaded yet
     // will be None for text bundles spawned when fonts have are already loaded
@@ -125,21 +196,165 @@ pub struct TextMeshState {
 
     warning_trigger_count: usize,
     warning_shown: bool,
-}
 
-impl Default for TextMeshState {
-    fn default() -> Self {
-        Self {
-            font_loaded: None,
-            warning_trigger_count: 0,
-            warning_shown: false,
-        }
-    }
+    // text mesh of the current geometry, with the color ignored
+    mesh_source: Option<TextMesh>,
+
+    // material was created by the plugin, and follows the style color
+    material_owned: bool,
 }
 
-fn apply_mesh(mesh_data: MeshData, mesh: &mut Mesh) {
+fn apply_mesh(mesh_data: MeshData, mesh: &mut Mesh, generate_tangents: bool) {
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, mesh_data.vertices);
     mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, mesh_data.normals);
     mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, mesh_data.uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, mesh_data.colors);
     mesh.set_indices(Some(Indices::U32(mesh_data.indices)));
+
+    mesh.remove_attribute(Mesh::ATTRIBUTE_TANGENT);
+    if generate_tangents {
+        if let Err(err) = mesh.generate_tangents() {
+            warn!("unable to generate text mesh tangents: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        mesh_data_generator::tests::get_fonts, TextMesh, TextMesh2dBundle, TextMeshBundle,
+    };
+
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.insert_resource(get_fonts())
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<Assets<ColorMaterial>>()
+            .init_resource::<MeshCache>()
+            .add_systems(Update, text_mesh);
+        app
+    }
+
+    #[test]
+    fn test_color_change() {
+        let mut app = app();
+        let entity = app
+            .world
+            .spawn(TextMeshBundle {
+                text_mesh: TextMesh::new_no_font("hello"),
+                ..Default::default()
+            })
+            .id();
+        app.update();
+
+        // geometry changes would restore the removed positions
+        let mesh = app.world.get::<Handle<Mesh>>(entity).unwrap().clone();
+        let mut meshes = app.world.resource_mut::<Assets<Mesh>>();
+        meshes
+            .get_mut(&mesh)
+            .unwrap()
+            .remove_attribute(Mesh::ATTRIBUTE_POSITION);
+
+        let set_color = |app: &mut App, entity: Entity| {
+            let mut text_mesh = app.world.get_mut::<TextMesh>(entity).unwrap();
+            text_mesh.style.color = Color::RED;
+            app.update();
+        };
+        set_color(&mut app, entity);
+
+        let material = app.world.get::<Handle<StandardMaterial>>(entity).unwrap();
+        let materials = app.world.resource::<Assets<StandardMaterial>>();
+        assert_eq!(materials.get(material).unwrap().base_color, Color::RED);
+
+        let meshes = app.world.resource::<Assets<Mesh>>();
+        let mesh = meshes.get(&mesh).unwrap();
+        assert!(mesh.attribute(Mesh::ATTRIBUTE_POSITION).is_none());
+
+        // user-supplied materials are left untouched
+        let mut materials = app.world.resource_mut::<Assets<StandardMaterial>>();
+        let material = materials.add(StandardMaterial::from(Color::BLUE));
+        let entity = app
+            .world
+            .spawn((
+                TextMeshBundle {
+                    text_mesh: TextMesh::new_no_font("hello"),
+                    ..Default::default()
+                },
+                material.clone(),
+            ))
+            .id();
+        app.update();
+        set_color(&mut app, entity);
+
+        let materials = app.world.resource::<Assets<StandardMaterial>>();
+        assert_eq!(materials.get(&material).unwrap().base_color, Color::BLUE);
+    }
+
+    #[test]
+    fn test_2d() {
+        let mut app = app();
+        let entity = app
+            .world
+            .spawn(TextMesh2dBundle {
+                text_mesh: TextMesh::new_no_font("hello"),
+                ..Default::default()
+            })
+            .id();
+        app.update();
+
+        assert!(app.world.get::<Mesh2dHandle>(entity).is_some());
+        assert!(app.world.get::<Handle<Mesh>>(entity).is_none());
+
+        let mut text_mesh = app.world.get_mut::<TextMesh>(entity).unwrap();
+        text_mesh.style.color = Color::RED;
+        app.update();
+
+        let material = app.world.get::<Handle<ColorMaterial>>(entity).unwrap();
+        let materials = app.world.resource::<Assets<ColorMaterial>>();
+        assert_eq!(materials.get(material).unwrap().color, Color::RED);
+    }
+
+    #[test]
+    fn test_tangents() {
+        let fonts = get_fonts();
+        let text_mesh = TextMesh::new_no_font("hello");
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        apply_mesh(
+            generate_text_mesh(&text_mesh, &fonts, None),
+            &mut mesh,
+            true,
+        );
+
+        let Some(bevy::render::mesh::VertexAttributeValues::Float32x4(tangents)) =
+            mesh.attribute(Mesh::ATTRIBUTE_TANGENT)
+        else {
+            panic!("tangents not generated");
+        };
+        let Some(bevy::render::mesh::VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        else {
+            unreachable!();
+        };
+
+        // tangents are perpendicular to the normals, and follow +u on the front face
+        for (tangent, normal) in tangents.iter().zip(normals) {
+            let tangent = Vec3::from_slice(tangent);
+            assert!(tangent.dot(Vec3::from(*normal)).abs() < 1e-3);
+
+            if normal[2] > 0.99 {
+                assert!(tangent.x > 0.99);
+            }
+        }
+
+        apply_mesh(
+            generate_text_mesh(&text_mesh, &fonts, None),
+            &mut mesh,
+            false,
+        );
+        assert!(mesh.attribute(Mesh::ATTRIBUTE_TANGENT).is_none());
+    }
 }
@@ -0,0 +1,1503 @@
+use bevy::prelude::*;
+use ttf_parser::{Face, GlyphId};
+use unicode_bidi::{BidiInfo, Level};
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+
+use crate::text_mesh::{
+    HorizontalAlign, SizeUnit, TextMesh, TextMeshAnchor, TextMeshDirection, TextMeshOverflow,
+    TextMeshStyle, TextMeshWritingMode, VerticalAlign, DEFAULT_FONT_SCALAR,
+};
+
+/// A glyph positioned by the layout
+#[derive(Debug)]
+pub(crate) struct LayoutGlyph {
+    pub glyph_id: GlyphId,
+
+    /// Index of the char in the text of all sections
+    pub index: usize,
+
+    /// Index of the [`LayoutSection`] the glyph belongs to
+    pub section: usize,
+
+    /// Glyph origin, on the baseline unless offset by the shaping, in world units
+    pub position: Vec2,
+
+    /// Sideways glyph of vertical text, rotated clockwise around the origin
+    pub rotated: bool,
+}
+
+/// A glyph of the shaped text of a section, in font units
+#[derive(Debug, Clone)]
+pub(crate) struct ShapedGlyph {
+    pub glyph_id: GlyphId,
+
+    /// Byte index of the first char of the glyph cluster in the section text
+    pub cluster: usize,
+
+    /// Advance along the line, downwards in vertical text
+    pub advance: i32,
+    pub offset: IVec2,
+
+    /// Bidi embedding level of the glyph, odd for right-to-left text
+    pub level: u8,
+
+    /// Bidi embedding level of the paragraph of the glyph
+    pub paragraph_level: u8,
+
+    /// Glyph of vertical text, which is laid out sideways (rotated clockwise)
+    pub rotated: bool,
+}
+
+/// Shapes the text of the sections into glyphs, in the logical order of the
+/// text. The text is split into runs of a single direction by the Unicode
+/// bidirectional algorithm, which is applied to the text of all sections.
+///
+/// Vertical text is split into upright runs (e.g. CJK), shaped top to bottom,
+/// and sideways runs (e.g. Latin), shaped left to right and rotated by the layout
+pub(crate) fn shape_sections(
+    sections: &mut [LayoutSection],
+    direction: &TextMeshDirection,
+    writing_mode: &TextMeshWritingMode,
+) {
+    let text = sections
+        .iter()
+        .map(|section| section.text.as_str())
+        .collect::<String>();
+
+    let vertical = *writing_mode == TextMeshWritingMode::Vertical;
+
+    // vertical text is not reordered
+    let base_level = match direction {
+        _ if vertical => Some(Level::ltr()),
+        TextMeshDirection::Auto => None,
+        TextMeshDirection::LeftToRight => Some(Level::ltr()),
+        TextMeshDirection::RightToLeft => Some(Level::rtl()),
+    };
+    let bidi = BidiInfo::new(&text, base_level);
+
+    let mut section_start = 0;
+    for section in sections {
+        let section_end = section_start + section.text.len();
+        let paragraph_level = |index: usize| {
+            bidi.paragraphs
+                .iter()
+                .find(|paragraph| paragraph.range.contains(&(section_start + index)))
+                .map_or(0, |paragraph| paragraph.level.number())
+        };
+
+        // level and orientation of each byte, whitespace has the orientation
+        // of the previous char
+        let mut upright = false;
+        let mut runs = Vec::with_capacity(section.text.len());
+        for (index, char) in section.text.char_indices() {
+            if !char.is_whitespace() {
+                upright = vertical && is_upright(char);
+            }
+
+            let level = match vertical {
+                true => Level::ltr(),
+                false => bidi.levels[section_start + index],
+            };
+            runs.resize(index + char.len_utf8(), (level, upright));
+        }
+
+        section.glyphs.clear();
+
+        let mut run_start = 0;
+        while run_start < runs.len() {
+            let (level, upright) = runs[run_start];
+            let run_end = runs[run_start..]
+                .iter()
+                .position(|run| *run != (level, upright))
+                .map_or(runs.len(), |length| run_start + length);
+
+            let direction = match (upright, level.is_rtl()) {
+                (true, _) => rustybuzz::Direction::TopToBottom,
+                (false, true) => rustybuzz::Direction::RightToLeft,
+                (false, false) => rustybuzz::Direction::LeftToRight,
+            };
+            let glyphs = shape(section.face, &section.text[run_start..run_end], direction);
+            section
+                .glyphs
+                .extend(glyphs.into_iter().map(|glyph| ShapedGlyph {
+                    cluster: run_start + glyph.cluster,
+                    level: level.number(),
+                    paragraph_level: paragraph_level(run_start),
+                    rotated: vertical && !upright,
+                    ..glyph
+                }));
+
+            run_start = run_end;
+        }
+
+        section_start = section_end;
+    }
+}
+
+/// Shapes a run of a single direction. Ligatures, contextual forms, mark
+/// positioning, mirroring, vertical forms and kerning are applied by the shaper
+fn shape(face: &Face, text: &str, direction: rustybuzz::Direction) -> Vec<ShapedGlyph> {
+    let shaper = rustybuzz::Face::from_face(face.clone());
+
+    let mut buffer = rustybuzz::UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.guess_segment_properties();
+    buffer.set_direction(direction);
+
+    let output = rustybuzz::shape(&shaper, &[], buffer);
+    let mut glyphs = output
+        .glyph_infos()
+        .iter()
+        .zip(output.glyph_positions())
+        .map(|(info, position)| ShapedGlyph {
+            glyph_id: GlyphId(info.glyph_id as u16),
+            cluster: info.cluster as usize,
+            // vertical advances (from the vmtx table if any) are negative
+            advance: match direction {
+                rustybuzz::Direction::TopToBottom => -position.y_advance,
+                _ => position.x_advance,
+            },
+            offset: IVec2::new(position.x_offset, position.y_offset),
+            level: 0,
+            paragraph_level: 0,
+            rotated: false,
+        })
+        .collect::<Vec<_>>();
+
+    // right-to-left runs are output in the visual order
+    if direction == rustybuzz::Direction::RightToLeft {
+        glyphs.reverse();
+    }
+
+    glyphs
+}
+
+/// Chars displayed upright in vertical text, by the blocks of mostly upright
+/// chars of the Unicode vertical orientation property (UAX #50)
+fn is_upright(char: char) -> bool {
+    matches!(char,
+        '\u{1100}'..='\u{11FF}' // Hangul Jamo
+        | '\u{2E80}'..='\u{2FFF}' // CJK radicals, Kangxi radicals
+        | '\u{3000}'..='\u{33FF}' // CJK symbols and punctuation, kana, enclosed CJK
+        | '\u{3400}'..='\u{4DBF}' // CJK extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK unified ideographs
+        | '\u{A000}'..='\u{A4CF}' // Yi
+        | '\u{AC00}'..='\u{D7AF}' // Hangul syllables
+        | '\u{F900}'..='\u{FAFF}' // CJK compatibility ideographs
+        | '\u{FE10}'..='\u{FE1F}' // vertical forms
+        | '\u{FE30}'..='\u{FE4F}' // CJK compatibility forms
+        | '\u{FF00}'..='\u{FFEF}' // halfwidth and fullwidth forms
+        | '\u{20000}'..='\u{3FFFF}' // CJK extensions
+    )
+}
+
+/// An underline or strikethrough bar, spanning a run of glyphs of a section
+#[derive(Debug)]
+pub(crate) struct LayoutDecoration {
+    pub section: usize,
+
+    /// Index of the first char of the run in the text of all sections
+    pub index: usize,
+
+    /// Top left corner of the bar, in world units
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+/// Glyphs and decorations positioned by the layout
+#[derive(Debug, Default)]
+pub(crate) struct TextLayout {
+    pub glyphs: Vec<LayoutGlyph>,
+    pub decorations: Vec<LayoutDecoration>,
+
+    /// Size of the laid out box, spanning from the origin towards +x and -y
+    pub size: Vec2,
+
+    /// Start of the first baseline: the left end of the first line, or the
+    /// top end of the center line of the first column
+    pub baseline: Vec2,
+}
+
+impl TextLayout {
+    /// Position of the anchor point of the laid out box
+    pub fn anchor(&self, anchor: &TextMeshAnchor) -> Vec2 {
+        match anchor {
+            TextMeshAnchor::BaselineLeft => self.baseline,
+            anchor => (anchor.as_vec() + Vec2::new(0.5, -0.5)) * self.size,
+        }
+    }
+}
+
+/// A run of text, laid out with a font of its own
+pub(crate) struct LayoutSection<'a> {
+    pub text: String,
+    pub face: &'a Face<'a>,
+
+    /// Shaped glyphs of the text, see [`shape_sections`]
+    pub glyphs: Vec<ShapedGlyph>,
+
+    /// Font size, `None` for the base font size
+    pub font_size: Option<SizeUnit>,
+
+    pub underline: bool,
+    pub strikethrough: bool,
+
+    /// Synthetic emboldening in em units, widening the glyph advances by twice the amount
+    pub embolden: f32,
+}
+
+impl<'a> LayoutSection<'a> {
+    /// Font size in world units, `base` being the base font size
+    pub fn scalar(&self, base: f32) -> f32 {
+        self.font_size
+            .as_ref()
+            .and_then(|size| size.as_scalar(base))
+            .unwrap_or(base)
+    }
+
+    /// Font size relative to the base font size, `None` for absolute sizes
+    fn relative_size(&self) -> Option<f32> {
+        match self.font_size {
+            None | Some(SizeUnit::Auto) => Some(1.),
+            Some(SizeUnit::Em(size)) => Some(size),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Lays out the (visible) glyphs of the sections into lines, and aligns them
+/// inside the `TextMeshSize` box. The box spans from the origin towards +x and -y.
+///
+/// Vertical text is laid out into columns from top to bottom, and the columns
+/// from right to left
+pub(crate) fn layout_text(
+    text_mesh: &TextMesh,
+    sections: &[LayoutSection],
+    scalar: f32,
+) -> TextLayout {
+    let vertical = text_mesh.style.writing_mode == TextMeshWritingMode::Vertical;
+    let metrics = SectionMetrics::new(sections, scalar, &text_mesh.style);
+    let line_height = text_mesh.style.line_height;
+
+    let (line_length, block_size) = box_size(text_mesh);
+
+    let wrap_length = line_length.filter(|_| text_mesh.size.wrapping);
+    let mut lines = break_lines(sections, &metrics, wrap_length);
+
+    match text_mesh.size.overflow {
+        TextMeshOverflow::Visible => (),
+        overflow => {
+            let line_count = match block_size {
+                Some(size) => fitting_lines(&lines, &metrics, line_height, size),
+                None => lines.len(),
+            };
+
+            let truncated = line_count < lines.len();
+            lines.truncate(line_count);
+
+            let last = lines.len().saturating_sub(1);
+            for (i, line) in lines.iter_mut().enumerate() {
+                let too_long = line_length.is_some_and(|length| line.width() > length);
+
+                if overflow == TextMeshOverflow::Ellipsis && (too_long || (truncated && i == last))
+                {
+                    line.truncate_with_ellipsis(&metrics, line_length);
+                } else if let Some(length) = line_length {
+                    line.clip(length);
+                }
+            }
+        }
+    }
+
+    for line in &mut lines {
+        line.reorder();
+    }
+
+    // with automatic sizing, the box shrinks to the laid out text
+    let box_length =
+        line_length.unwrap_or_else(|| lines.iter().map(Line::width).fold(0., f32::max));
+
+    // all but the last lines of the paragraphs are justified
+    if !vertical && text_mesh.alignment.horizontal == HorizontalAlign::Justify {
+        for line in lines.iter_mut().filter(|line| line.wrapped) {
+            line.justify(box_length);
+        }
+    }
+
+    // from the start of the first line to the descender of the last line
+    let (baselines, block_extent) = baselines(&lines, &metrics, line_height);
+    let box_size = block_size.unwrap_or(block_extent);
+
+    // offset of the block from the start of the box
+    let block_offset = match vertical {
+        false => match text_mesh.alignment.vertical {
+            VerticalAlign::Top => 0.,
+            VerticalAlign::Center => (box_size - block_extent) / 2.,
+            VerticalAlign::Bottom => box_size - block_extent,
+        },
+        // columns start from the right
+        true => match text_mesh.alignment.horizontal {
+            HorizontalAlign::Right => 0.,
+            HorizontalAlign::Center => (box_size - block_extent) / 2.,
+            HorizontalAlign::Left | HorizontalAlign::Justify => box_size - block_extent,
+        },
+    };
+
+    let first_baseline = block_offset + baselines.first().copied().unwrap_or_default();
+    let mut layout = TextLayout {
+        size: match vertical {
+            false => Vec2::new(box_length, box_size),
+            true => Vec2::new(box_size, box_length),
+        },
+        baseline: match vertical {
+            false => Vec2::new(0., -first_baseline),
+            true => Vec2::new(box_size - first_baseline, 0.),
+        },
+        ..Default::default()
+    };
+
+    for (line, baseline) in lines.iter().zip(baselines) {
+        // offset of the line from the start of the box
+        let line_offset = match vertical {
+            false => {
+                // left and right alignments are mirrored in right-to-left
+                // paragraphs, and lines not justified are aligned to the start
+                let horizontal = match (
+                    &text_mesh.alignment.horizontal,
+                    line.paragraph_level % 2 == 1,
+                ) {
+                    (HorizontalAlign::Left | HorizontalAlign::Justify, true) => {
+                        HorizontalAlign::Right
+                    }
+                    (HorizontalAlign::Right, true) => HorizontalAlign::Left,
+                    (horizontal, _) => *horizontal,
+                };
+                match horizontal {
+                    HorizontalAlign::Left | HorizontalAlign::Justify => 0.,
+                    HorizontalAlign::Center => (box_length - line.width()) / 2.,
+                    HorizontalAlign::Right => box_length - line.width(),
+                }
+            }
+            true => match text_mesh.alignment.vertical {
+                VerticalAlign::Top => 0.,
+                VerticalAlign::Center => (box_length - line.width()) / 2.,
+                VerticalAlign::Bottom => box_length - line.width(),
+            },
+        };
+
+        // maps a position along the line, and across the line from its baseline
+        // (upwards in horizontal text, rightwards in vertical text), to world units
+        let baseline = block_offset + baseline;
+        let to_world = |along: f32, across: f32| match vertical {
+            false => Vec2::new(line_offset + along, across - baseline),
+            true => Vec2::new(box_size - baseline + across, -(line_offset + along)),
+        };
+
+        for decoration in line.decorations(sections, &metrics) {
+            // bars of vertical text are rotated with the sideways glyphs
+            let (position, size) = match vertical {
+                false => (
+                    to_world(decoration.position.x, decoration.position.y),
+                    decoration.size,
+                ),
+                true => {
+                    let across = decoration.position.y - metrics[decoration.section].center;
+                    (
+                        to_world(decoration.position.x, across - decoration.size.y),
+                        Vec2::new(decoration.size.y, decoration.size.x),
+                    )
+                }
+            };
+
+            layout.decorations.push(LayoutDecoration {
+                position,
+                size,
+                ..decoration
+            });
+        }
+
+        layout.glyphs.extend(
+            line.glyphs
+                .iter()
+                .filter(|glyph| !glyph.char.is_whitespace())
+                .map(|glyph| {
+                    let position = match glyph.rotated {
+                        // sideways glyphs are centered on the column, by the
+                        // middle of the ascender and the descender
+                        true => {
+                            let across = -metrics[glyph.section].center;
+                            to_world(glyph.x, across) + Vec2::new(glyph.offset.y, -glyph.offset.x)
+                        }
+                        false => to_world(glyph.x, 0.) + glyph.offset,
+                    };
+
+                    LayoutGlyph {
+                        glyph_id: glyph.glyph_id,
+                        index: glyph.index,
+                        section: glyph.section,
+                        position,
+                        rotated: glyph.rotated,
+                    }
+                }),
+        );
+    }
+
+    layout
+}
+
+/// Extents of the `TextMeshSize` box in world units, if not automatic: the
+/// length of the lines, and the size of the block of lines. Horizontal lines
+/// span the width, vertical columns the height
+fn box_size(text_mesh: &TextMesh) -> (Option<f32>, Option<f32>) {
+    // em sizes can not be relative to an automatic font size, that depends on the box
+    let em = text_mesh
+        .style
+        .font_size
+        .as_scalar(DEFAULT_FONT_SCALAR)
+        .unwrap_or(DEFAULT_FONT_SCALAR);
+
+    let width = text_mesh.size.width.as_scalar(em);
+    let height = text_mesh.size.height.as_scalar(em);
+
+    match text_mesh.style.writing_mode {
+        TextMeshWritingMode::Horizontal => (width, height),
+        TextMeshWritingMode::Vertical => (height, width),
+    }
+}
+
+/// Finds the largest base font size scalar, with which the text fits into the
+/// `TextMeshSize` box, within the `TextMeshStyle::font_size_bounds`
+pub(crate) fn fit_font_size(text_mesh: &TextMesh, sections: &[LayoutSection]) -> f32 {
+    let bounds = &text_mesh.style.font_size_bounds;
+    let line_height = text_mesh.style.line_height;
+    let vertical = text_mesh.style.writing_mode == TextMeshWritingMode::Vertical;
+
+    let (line_length, block_size) = box_size(text_mesh);
+
+    let fits = |scalar: f32| {
+        let metrics = SectionMetrics::new(sections, scalar, &text_mesh.style);
+        let wrap_length = line_length.filter(|_| text_mesh.size.wrapping);
+        let lines = break_lines(sections, &metrics, wrap_length);
+
+        let fits_length = match line_length {
+            Some(length) => lines
+                .iter()
+                .all(|line| !line.split_word && line.width() <= length),
+            None => true,
+        };
+        let fits_size = match block_size {
+            Some(size) => fitting_lines(&lines, &metrics, line_height, size) >= lines.len(),
+            None => true,
+        };
+
+        fits_length && fits_size
+    };
+
+    // a single line box, or the longest glyph, of the sections scaling with
+    // the base font size limits the size
+    let relative = sections
+        .iter()
+        .filter(|section| !section.text.is_empty())
+        .filter_map(|section| Some((section, section.relative_size()?)));
+
+    let limit = match (line_length, block_size) {
+        (_, Some(block_size)) => relative
+            .map(|(section, size)| {
+                let metrics = LineMetrics::new(section.face, size, vertical);
+                block_size / (metrics.ascent - metrics.descent)
+            })
+            .reduce(f32::min),
+        (Some(line_length), None) => relative
+            .map(|(section, size)| {
+                let glyphs = GlyphMetrics::new(section, size, &text_mesh.style);
+                let longest = section
+                    .glyphs
+                    .iter()
+                    .map(|glyph| glyphs.shaped_advance(glyph))
+                    .fold(0., f32::max);
+
+                line_length / longest
+            })
+            .filter(|limit| limit.is_finite())
+            .reduce(f32::min),
+        // nothing to fit into
+        (None, None) => None,
+    };
+    let limit = limit.unwrap_or(DEFAULT_FONT_SCALAR);
+
+    let max = bounds
+        .max
+        .as_ref()
+        .and_then(|max| max.as_scalar(DEFAULT_FONT_SCALAR));
+    let min = bounds
+        .min
+        .as_ref()
+        .and_then(|min| min.as_scalar(DEFAULT_FONT_SCALAR));
+
+    let mut high = max.map_or(limit, |max| max.min(limit));
+    let mut low = min.unwrap_or(high / 1000.);
+
+    if low >= high || !fits(low) {
+        return low;
+    }
+
+    if fits(high) {
+        return high;
+    }
+
+    for _ in 0..16 {
+        let scalar = (low + high) / 2.;
+
+        if fits(scalar) {
+            low = scalar;
+        } else {
+            high = scalar;
+        }
+    }
+
+    low
+}
+
+/// Splits the shaped text into lines at the mandatory line breaks, and wraps
+/// lines longer than `max_width` at the Unicode (UAX #14) line break opportunities
+fn break_lines(
+    sections: &[LayoutSection],
+    metrics: &[SectionMetrics],
+    max_width: Option<f32>,
+) -> Vec<Line> {
+    // line breaking opportunities depend on the text around section boundaries
+    let text = sections
+        .iter()
+        .map(|section| section.text.as_str())
+        .collect::<String>();
+
+    let mut lines = vec![Line::default()];
+    let mut opportunities = linebreaks(&text).peekable();
+    let mut chars = text.char_indices().enumerate().peekable();
+
+    // glyph index of the current line, where the line may be wrapped
+    let mut wrap_at = None;
+    let mut section_start = 0;
+
+    for (section, layout_section) in sections.iter().enumerate() {
+        let glyphs = &metrics[section].glyphs;
+
+        for shaped in &layout_section.glyphs {
+            let index = section_start + shaped.cluster;
+
+            // first char of the glyph cluster
+            while chars.next_if(|(_, (i, _))| *i < index).is_some() {}
+            let Some(&(char_index, (_, char))) = chars.peek() else {
+                continue;
+            };
+
+            // opportunities are reported at the start of the text after the break
+            while let Some((_, opportunity)) = opportunities.next_if(|(i, _)| *i <= index) {
+                match opportunity {
+                    BreakOpportunity::Mandatory => {
+                        lines.push(Line::new(section));
+                        wrap_at = None;
+                    }
+                    BreakOpportunity::Allowed => wrap_at = Some(lines.last().unwrap().glyphs.len()),
+                }
+            }
+
+            if lines.last().unwrap().glyphs.is_empty() {
+                lines.last_mut().unwrap().section = section;
+            }
+
+            if is_line_terminator(char) {
+                continue;
+            }
+
+            let advance = match is_word_separator(char) {
+                true => glyphs.shaped_advance(shaped) + glyphs.word_spacing,
+                false => glyphs.shaped_advance(shaped),
+            };
+            let glyph = LineGlyph {
+                char,
+                index: char_index,
+                glyph_id: shaped.glyph_id,
+                section,
+                x: 0.,
+                advance,
+                letter_spacing: glyphs.letter_spacing(advance),
+                offset: shaped.offset.as_vec2() * glyphs.scale,
+                level: shaped.level,
+                rotated: shaped.rotated,
+                tab_width: (char == '\t').then_some(glyphs.tab_width),
+            };
+            let line = lines.last_mut().unwrap();
+            if line.glyphs.is_empty() {
+                line.paragraph_level = shaped.paragraph_level;
+            }
+
+            if let Some(max_width) = max_width {
+                if !char.is_whitespace()
+                    && line.pen + glyph.advance - glyph.letter_spacing > max_width
+                    && line.width() > 0.
+                {
+                    // wrap the word to the next line, or split the word if it
+                    // does not fit to a line of its own
+                    let word_start = wrap_at.filter(|&i| i > 0);
+                    let start = word_start.unwrap_or(line.glyphs.len());
+                    let wrapped = line.glyphs.split_off(start);
+                    line.wrapped = true;
+
+                    let mut next = Line {
+                        split_word: word_start.is_none(),
+                        paragraph_level: line.paragraph_level,
+                        ..Line::new(wrapped.first().map_or(section, |glyph| glyph.section))
+                    };
+                    for glyph in wrapped {
+                        next.push(glyph);
+                    }
+
+                    lines.push(next);
+                    wrap_at = None;
+                }
+            }
+
+            lines.last_mut().unwrap().push(glyph);
+        }
+
+        section_start += layout_section.text.len();
+    }
+
+    lines
+}
+
+/// Distances of the baselines from the top of the text block, and the height
+/// of the block, from the top of the first line to the descender of the last line
+fn baselines(lines: &[Line], metrics: &[SectionMetrics], line_height: f32) -> (Vec<f32>, f32) {
+    let mut baselines = Vec::with_capacity(lines.len());
+    let mut previous: Option<LineMetrics> = None;
+    let mut baseline = 0.;
+
+    for line in lines {
+        let current = line.metrics(metrics);
+
+        baseline += match previous {
+            // lines are spaced by the (multiplied) natural line height of the fonts
+            Some(previous) => {
+                (current.ascent - previous.descent + current.line_gap.max(previous.line_gap))
+                    * line_height
+            }
+            None => current.ascent,
+        };
+
+        baselines.push(baseline);
+        previous = Some(current);
+    }
+
+    let height = baseline - previous.map_or(0., |metrics| metrics.descent);
+
+    (baselines, height)
+}
+
+/// Count of whole lines that fit into the height
+fn fitting_lines(
+    lines: &[Line],
+    metrics: &[SectionMetrics],
+    line_height: f32,
+    height: f32,
+) -> usize {
+    let (baselines, _) = baselines(lines, metrics, line_height);
+
+    lines
+        .iter()
+        .zip(baselines)
+        .take_while(|(line, baseline)| baseline - line.metrics(metrics).descent <= height)
+        .count()
+}
+
+fn is_line_terminator(char: char) -> bool {
+    matches!(
+        char,
+        '\n' | '\r' | '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}'
+    )
+}
+
+/// Chars widened by the word spacing, as in CSS
+fn is_word_separator(char: char) -> bool {
+    matches!(
+        char,
+        ' ' | '\u{A0}' | '\u{1361}' | '\u{10100}' | '\u{10101}' | '\u{1039F}' | '\u{1091F}'
+    )
+}
+
+#[derive(Debug)]
+struct LineGlyph {
+    char: char,
+    index: usize,
+    glyph_id: GlyphId,
+    section: usize,
+
+    /// Glyph origin from the start of the line
+    x: f32,
+    advance: f32,
+
+    /// Letter spacing included in the advance, which is left out at the end of the line
+    letter_spacing: f32,
+
+    /// Offset from the glyph origin, e.g. of a combining mark
+    offset: Vec2,
+
+    /// Bidi embedding level
+    level: u8,
+
+    /// Sideways glyph of vertical text
+    rotated: bool,
+
+    /// Distance between the tab stops, for tabs. The advance of a tab depends
+    /// on its position on the line
+    tab_width: Option<f32>,
+}
+
+#[derive(Debug, Default)]
+struct Line {
+    glyphs: Vec<LineGlyph>,
+
+    /// Section at the start of the line, which defines the height of an empty line
+    section: usize,
+
+    /// The line starts from the middle of a word, that did not fit to a line of its own
+    split_word: bool,
+
+    /// Pen position after the last glyph
+    pen: f32,
+
+    /// Bidi embedding level of the paragraph of the line
+    paragraph_level: u8,
+
+    /// The paragraph continues on the next line
+    wrapped: bool,
+}
+
+impl Line {
+    fn new(section: usize) -> Self {
+        Self {
+            section,
+            ..Default::default()
+        }
+    }
+
+    /// Reorders the glyphs from the logical order to the visual order, by the
+    /// rule L2 of the Unicode bidirectional algorithm. Trailing whitespace is dropped
+    fn reorder(&mut self) {
+        while self
+            .glyphs
+            .last()
+            .is_some_and(|glyph| glyph.char.is_whitespace())
+        {
+            self.glyphs.pop();
+        }
+
+        let levels = self.glyphs.iter().map(|glyph| glyph.level);
+        let highest = levels.clone().max().unwrap_or_default();
+        let lowest_odd = levels.min().unwrap_or_default() | 1;
+
+        // from the highest level to the lowest odd level, the runs at or above
+        // the level are reversed
+        for level in (lowest_odd..=highest).rev() {
+            let mut start = 0;
+            while start < self.glyphs.len() {
+                if self.glyphs[start].level < level {
+                    start += 1;
+                    continue;
+                }
+
+                let length = self.glyphs[start..]
+                    .iter()
+                    .position(|glyph| glyph.level < level)
+                    .unwrap_or(self.glyphs.len() - start);
+                self.glyphs[start..start + length].reverse();
+                start += length;
+            }
+        }
+
+        let glyphs = std::mem::take(&mut self.glyphs);
+        self.pen = 0.;
+        for glyph in glyphs {
+            self.push(glyph);
+        }
+    }
+
+    /// Appends the glyph at the pen position. Tabs advance to the next tab stop
+    fn push(&mut self, glyph: LineGlyph) {
+        let advance = match glyph.tab_width {
+            Some(tab_width) if tab_width > 0. => {
+                ((self.pen / tab_width).floor() + 1.) * tab_width - self.pen
+            }
+            _ => glyph.advance,
+        };
+
+        self.glyphs.push(LineGlyph {
+            x: self.pen,
+            advance,
+            ..glyph
+        });
+        self.pen += advance;
+    }
+
+    /// Largest vertical metrics of the fonts on the line
+    fn metrics(&self, metrics: &[SectionMetrics]) -> LineMetrics {
+        let mut sections = self.glyphs.iter().map(|glyph| glyph.section).peekable();
+        if sections.peek().is_none() {
+            return metrics[self.section].line.clone();
+        }
+
+        sections
+            .map(|section| metrics[section].line.clone())
+            .reduce(|a, b| LineMetrics {
+                ascent: a.ascent.max(b.ascent),
+                descent: a.descent.min(b.descent),
+                line_gap: a.line_gap.max(b.line_gap),
+            })
+            .unwrap()
+    }
+
+    /// Widens the spaces between the words, so that the line spans the
+    /// length. Must be called after [`Line::reorder`]
+    fn justify(&mut self, length: f32) {
+        let first = self.glyphs.iter().position(|g| !g.char.is_whitespace());
+        let last = self.glyphs.iter().rposition(|g| !g.char.is_whitespace());
+        let (Some(first), Some(last)) = (first, last) else {
+            return;
+        };
+
+        let inner = first..last;
+        let separators = self.glyphs[inner.clone()]
+            .iter()
+            .filter(|glyph| is_word_separator(glyph.char))
+            .count();
+        let extra = length - self.width();
+        if separators == 0 || extra <= 0. {
+            return;
+        }
+
+        let glyphs = std::mem::take(&mut self.glyphs);
+        self.pen = 0.;
+        for (i, glyph) in glyphs.into_iter().enumerate() {
+            let advance = match inner.contains(&i) && is_word_separator(glyph.char) {
+                true => glyph.advance + extra / separators as f32,
+                false => glyph.advance,
+            };
+            self.push(LineGlyph { advance, ..glyph });
+        }
+    }
+
+    /// Extent of the line, trailing whitespace and letter spacing excluded
+    fn width(&self) -> f32 {
+        self.glyphs
+            .iter()
+            .rev()
+            .find(|glyph| !glyph.char.is_whitespace())
+            .map_or(0., |glyph| glyph.x + glyph.advance - glyph.letter_spacing)
+    }
+
+    /// Underline and strikethrough bars of the line, from `origin` on the baseline.
+    /// The bars are split at section boundaries, and exclude trailing whitespace
+    /// and letter spacing
+    fn decorations(
+        &self,
+        sections: &[LayoutSection],
+        metrics: &[SectionMetrics],
+    ) -> Vec<LayoutDecoration> {
+        let width = self.width();
+        let mut decorations = Vec::new();
+        let mut glyphs = self
+            .glyphs
+            .iter()
+            .take_while(|glyph| glyph.x < width)
+            .peekable();
+
+        while let Some(first) = glyphs.next() {
+            let mut last = first;
+            while let Some(glyph) = glyphs.next_if(|glyph| glyph.section == first.section) {
+                last = glyph;
+            }
+
+            let section = &sections[first.section];
+            let section_metrics = &metrics[first.section].glyphs;
+
+            let bars = [
+                (section.underline, section_metrics.underline()),
+                (section.strikethrough, section_metrics.strikethrough()),
+            ];
+
+            for (_, (position, thickness)) in bars.into_iter().filter(|(enabled, _)| *enabled) {
+                decorations.push(LayoutDecoration {
+                    section: first.section,
+                    index: first.index,
+                    position: Vec2::new(first.x, position),
+                    size: Vec2::new((last.x + last.advance).min(width) - first.x, thickness),
+                });
+            }
+        }
+
+        decorations
+    }
+
+    /// Drops the glyphs that do not fit into the width
+    fn clip(&mut self, width: f32) {
+        if let Some(index) = self
+            .glyphs
+            .iter()
+            .position(|glyph| glyph.x + glyph.advance - glyph.letter_spacing > width)
+        {
+            self.glyphs.truncate(index);
+        }
+    }
+
+    /// Drops glyphs from the end of the line, until an ellipsis fits into the width.
+    /// In vertical text, the ellipsis is upright if the glyph at the end of the
+    /// column is
+    fn truncate_with_ellipsis(&mut self, metrics: &[SectionMetrics], width: Option<f32>) {
+        let section = self
+            .glyphs
+            .last()
+            .map_or(self.section, |glyph| glyph.section);
+        let glyphs = &metrics[section].glyphs;
+        let vertical = metrics[section].vertical;
+        let upright = vertical && self.glyphs.last().is_some_and(|glyph| !glyph.rotated);
+
+        let text = match glyphs.face.glyph_index('…') {
+            Some(_) => "…",
+            None => "...",
+        };
+
+        // chars with their glyphs, advances and offsets
+        let ellipsis: Vec<_> = match upright {
+            true => shape(glyphs.face, text, rustybuzz::Direction::TopToBottom)
+                .iter()
+                .zip(text.chars())
+                .map(|(glyph, char)| {
+                    let advance = glyphs.shaped_advance(glyph);
+                    (char, glyph.glyph_id, advance, glyph.offset)
+                })
+                .collect(),
+            false => text
+                .chars()
+                .map(|char| {
+                    let (char, glyph_id) = glyphs.lookup(char);
+                    (char, glyph_id, glyphs.advance(glyph_id), IVec2::ZERO)
+                })
+                .collect(),
+        };
+
+        let ellipsis_width: f32 = ellipsis.iter().map(|(_, _, advance, _)| advance).sum();
+        let width = width.unwrap_or(f32::MAX);
+
+        while let Some(last) = self.glyphs.last() {
+            if !last.char.is_whitespace() && self.width() + ellipsis_width <= width {
+                break;
+            }
+
+            self.glyphs.pop();
+        }
+
+        self.pen = self
+            .glyphs
+            .last()
+            .map_or(0., |glyph| glyph.x + glyph.advance);
+
+        // the ellipsis stands for the first dropped char
+        let index = self.glyphs.last().map_or(0, |glyph| glyph.index + 1);
+        for (char, glyph_id, advance, offset) in ellipsis {
+            self.push(LineGlyph {
+                char,
+                index,
+                glyph_id,
+                section,
+                x: 0.,
+                advance,
+                letter_spacing: glyphs.letter_spacing(advance),
+                offset: offset.as_vec2() * glyphs.scale,
+                level: self.paragraph_level,
+                rotated: vertical && !upright,
+                tab_width: None,
+            });
+        }
+    }
+}
+
+/// Horizontal glyph metrics, scaled to world units
+struct GlyphMetrics<'a> {
+    face: &'a Face<'a>,
+    scale: f32,
+
+    /// Added to the advances of all glyphs: the letter spacing, and the
+    /// widening of emboldened glyphs
+    extra_advance: f32,
+
+    /// Letter spacing part of the extra advance
+    letter_spacing: f32,
+
+    /// Added to the advances of word separators
+    word_spacing: f32,
+
+    /// Distance between the tab stops
+    tab_width: f32,
+}
+
+impl<'a> GlyphMetrics<'a> {
+    fn new(section: &'a LayoutSection<'a>, scalar: f32, style: &TextMeshStyle) -> Self {
+        let spacing = |size: &SizeUnit| size.as_scalar(scalar).unwrap_or_default();
+
+        let letter_spacing = spacing(&style.letter_spacing);
+
+        Self {
+            face: section.face,
+            scale: scalar / section.face.units_per_em() as f32,
+            extra_advance: 2. * section.embolden * scalar + letter_spacing,
+            letter_spacing,
+            word_spacing: spacing(&style.word_spacing),
+            tab_width: spacing(&style.tab_width),
+        }
+    }
+
+    /// Glyph for the char, or the .notdef glyph if the font does not have one
+    fn lookup(&self, char: char) -> (char, GlyphId) {
+        match self.face.glyph_index(char) {
+            Some(glyph_id) => (char, glyph_id),
+            // whitespace without a glyph of its own still takes up room
+            None if char.is_whitespace() => (' ', self.face.glyph_index(' ').unwrap_or_default()),
+            None => (char, GlyphId(0)),
+        }
+    }
+
+    /// Position of the top of the underline from the baseline, and its thickness
+    fn underline(&self) -> (f32, f32) {
+        match self.face.underline_metrics() {
+            Some(metrics) => (
+                metrics.position as f32 * self.scale,
+                metrics.thickness as f32 * self.scale,
+            ),
+            None => self.em_scaled(-0.1, 0.05),
+        }
+    }
+
+    /// Position of the top of the strikethrough from the baseline, and its thickness
+    fn strikethrough(&self) -> (f32, f32) {
+        match self.face.strikeout_metrics() {
+            Some(metrics) => (
+                metrics.position as f32 * self.scale,
+                metrics.thickness as f32 * self.scale,
+            ),
+            None => self.em_scaled(0.3, 0.05),
+        }
+    }
+
+    fn em_scaled(&self, position: f32, thickness: f32) -> (f32, f32) {
+        let em = self.face.units_per_em() as f32 * self.scale;
+        (position * em, thickness * em)
+    }
+
+    fn advance(&self, glyph_id: GlyphId) -> f32 {
+        self.face.glyph_hor_advance(glyph_id).unwrap_or_default() as f32 * self.scale
+            + self.extra_advance
+    }
+
+    /// Letter spacing included in the advance of a glyph
+    fn letter_spacing(&self, advance: f32) -> f32 {
+        match advance == 0. {
+            true => 0.,
+            false => self.letter_spacing,
+        }
+    }
+
+    /// Advance of a shaped glyph, which includes the kerning. Glyphs without
+    /// an advance (marks) are not widened
+    fn shaped_advance(&self, glyph: &ShapedGlyph) -> f32 {
+        match glyph.advance {
+            0 => 0.,
+            advance => advance as f32 * self.scale + self.extra_advance,
+        }
+    }
+}
+
+/// Glyph and line metrics of a section, scaled to world units
+struct SectionMetrics<'a> {
+    glyphs: GlyphMetrics<'a>,
+    line: LineMetrics,
+
+    /// Middle of the ascender and the descender, from the baseline
+    center: f32,
+
+    /// Vertical text, with the lines as columns
+    vertical: bool,
+}
+
+impl<'a> SectionMetrics<'a> {
+    fn new(sections: &'a [LayoutSection<'a>], scalar: f32, style: &TextMeshStyle) -> Vec<Self> {
+        let vertical = style.writing_mode == TextMeshWritingMode::Vertical;
+
+        sections
+            .iter()
+            .map(|section| {
+                let scalar = section.scalar(scalar);
+                let horizontal = LineMetrics::new(section.face, scalar, false);
+
+                Self {
+                    glyphs: GlyphMetrics::new(section, scalar, style),
+                    line: LineMetrics::new(section.face, scalar, vertical),
+                    center: (horizontal.ascent + horizontal.descent) / 2.,
+                    vertical,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Font metrics across the lines, scaled to world units. Vertical columns are
+/// measured from their center line, with the ascent towards the right
+#[derive(Debug, Clone)]
+struct LineMetrics {
+    /// Distance from the baseline to the top of the line box
+    ascent: f32,
+    /// Distance from the baseline to the bottom of the line box, negative
+    descent: f32,
+    /// Extra distance between two consecutive lines
+    line_gap: f32,
+}
+
+impl LineMetrics {
+    fn new(face: &Face, scalar: f32, vertical: bool) -> Self {
+        let scale = scalar / face.units_per_em() as f32;
+
+        if vertical {
+            // the vhea metrics, or an em wide column without them
+            let half_em = face.units_per_em() as i16 / 2;
+
+            return Self {
+                ascent: face.vertical_ascender().unwrap_or(half_em) as f32 * scale,
+                descent: face.vertical_descender().unwrap_or(-half_em) as f32 * scale,
+                line_gap: face.vertical_line_gap().unwrap_or_default() as f32 * scale,
+            };
+        }
+
+        // ttf-parser prefers the OS/2 typographic metrics, if the font says so
+        Self {
+            ascent: face.ascender() as f32 * scale,
+            descent: face.descender() as f32 * scale,
+            line_gap: face.line_gap() as f32 * scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        mesh_data_generator::tests::get_font_bytes, SizeUnit, TextMeshSize, TextMeshStyle,
+    };
+
+    use super::*;
+
+    fn section<'a>(text: &str, face: &'a Face<'a>) -> LayoutSection<'a> {
+        styled_section(text, face, &TextMeshStyle::default())
+    }
+
+    fn styled_section<'a>(
+        text: &str,
+        face: &'a Face<'a>,
+        style: &TextMeshStyle,
+    ) -> LayoutSection<'a> {
+        let mut sections = [LayoutSection {
+            text: text.to_string(),
+            face,
+            glyphs: Vec::new(),
+            font_size: None,
+            underline: false,
+            strikethrough: false,
+            embolden: 0.,
+        }];
+        shape_sections(&mut sections, &style.direction, &style.writing_mode);
+
+        let [section] = sections;
+        section
+    }
+
+    /// Laid out chars with their baselines
+    fn layout(text_mesh: &TextMesh) -> Vec<(f32, char)> {
+        let bytes = get_font_bytes();
+        let face = Face::parse(&bytes, 0).unwrap();
+
+        // chars of the glyphs, the text has no ligatures
+        let char = |glyph_id| {
+            text_mesh
+                .text
+                .chars()
+                .chain(['…'])
+                .find(|char| face.glyph_index(*char) == Some(glyph_id))
+                .unwrap()
+        };
+
+        layout_text(text_mesh, &[section(&text_mesh.text, &face)], 1.)
+            .glyphs
+            .into_iter()
+            .map(|glyph| (glyph.position.y, char(glyph.glyph_id)))
+            .collect()
+    }
+
+    fn lines(glyphs: &[(f32, char)]) -> Vec<String> {
+        let mut lines: Vec<(f32, String)> = Vec::new();
+
+        for &(baseline, char) in glyphs {
+            match lines.last_mut() {
+                Some((y, line)) if *y == baseline => line.push(char),
+                _ => lines.push((baseline, char.to_string())),
+            }
+        }
+
+        lines.into_iter().map(|(_, line)| line).collect()
+    }
+
+    // font size of 72 scales one em to one world unit, FiraMono advances are 0.6em
+    fn sized_text(text: &str, width: f32, height: f32, overflow: TextMeshOverflow) -> TextMesh {
+        TextMesh {
+            text: text.to_string(),
+            size: TextMeshSize {
+                width: SizeUnit::NonStandard(72. * width),
+                height: SizeUnit::NonStandard(72. * height),
+                overflow,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_word_wrapping() {
+        let text_mesh = sized_text("hello big world", 6., 10., TextMeshOverflow::Visible);
+        assert_eq!(lines(&layout(&text_mesh)), ["hellobig", "world"]);
+
+        // too long words are split
+        let text_mesh = sized_text("hello", 2., 10., TextMeshOverflow::Visible);
+        assert_eq!(lines(&layout(&text_mesh)), ["hel", "lo"]);
+
+        let text_mesh = sized_text("hello\nworld", 10., 10., TextMeshOverflow::Visible);
+        assert_eq!(lines(&layout(&text_mesh)), ["hello", "world"]);
+    }
+
+    #[test]
+    fn test_overflow() {
+        // three lines, of which two fit into the height (1.2em per line)
+        let text = "hello big world";
+
+        let text_mesh = sized_text(text, 3.5, 2.5, TextMeshOverflow::Visible);
+        assert_eq!(lines(&layout(&text_mesh)), ["hello", "big", "world"]);
+
+        let text_mesh = sized_text(text, 3.5, 2.5, TextMeshOverflow::Clip);
+        assert_eq!(lines(&layout(&text_mesh)), ["hello", "big"]);
+
+        let text_mesh = sized_text(text, 3.5, 2.5, TextMeshOverflow::Ellipsis);
+        assert_eq!(lines(&layout(&text_mesh)), ["hello", "big…"]);
+
+        // without wrapping, the lines are cut at the width
+        let mut text_mesh = sized_text(text, 3.5, 2.5, TextMeshOverflow::Clip);
+        text_mesh.size.wrapping = false;
+        assert_eq!(lines(&layout(&text_mesh)), ["hello"]);
+
+        text_mesh.size.overflow = TextMeshOverflow::Ellipsis;
+        assert_eq!(lines(&layout(&text_mesh)), ["hell…"]);
+    }
+
+    #[test]
+    fn test_spacing() {
+        let bytes = get_font_bytes();
+        let face = Face::parse(&bytes, 0).unwrap();
+
+        let text_mesh = TextMesh {
+            text: "ab c\td".to_string(),
+            style: TextMeshStyle {
+                letter_spacing: SizeUnit::Em(0.1),
+                word_spacing: SizeUnit::Em(0.2),
+                tab_width: SizeUnit::Em(2.),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // advances of 0.7em, the space of 0.9em, and the tab reaching to 4em
+        let positions = layout_text(&text_mesh, &[section(&text_mesh.text, &face)], 1.)
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.position.x)
+            .collect::<Vec<_>>();
+        for (position, expected) in positions.iter().zip([0., 0.7, 2.3, 4.]) {
+            assert!((position - expected).abs() < 1e-5, "{positions:?}");
+        }
+        assert_eq!(positions.len(), 4);
+
+        // the spacing after the last glyph is not part of the line
+        let mut text_mesh = sized_text("ll", 4., 10., TextMeshOverflow::Visible);
+        text_mesh.style.letter_spacing = SizeUnit::Em(0.5);
+        text_mesh.alignment.horizontal = HorizontalAlign::Center;
+
+        let mut section = section(&text_mesh.text, &face);
+        section.underline = true;
+        let layout = layout_text(&text_mesh, &[section], 1.);
+
+        let start = layout.glyphs[0].position.x;
+        let end = layout.glyphs[1].position.x + 0.6;
+        assert!(((start + end) / 2. - 2.).abs() < 1e-5, "{start} {end}");
+
+        let underline = &layout.decorations[0];
+        assert!((underline.position.x - start).abs() < 1e-5);
+        assert!((underline.size.x - (end - start)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_justify() {
+        let bytes = get_font_bytes();
+        let face = Face::parse(&bytes, 0).unwrap();
+
+        let mut text_mesh = sized_text("hello big world", 6., 10., TextMeshOverflow::Visible);
+        text_mesh.alignment.horizontal = HorizontalAlign::Justify;
+
+        let glyphs = layout_text(&text_mesh, &[section(&text_mesh.text, &face)], 1.).glyphs;
+        let x = |index: usize| {
+            let glyph = glyphs.iter().find(|glyph| glyph.index == index).unwrap();
+            glyph.position.x
+        };
+
+        // "big" is moved to the end of the first line, the last line is not justified
+        assert!((x(8) + 0.6 - 6.).abs() < 1e-5);
+        assert_eq!(x(10), 0.);
+    }
+
+    #[test]
+    fn test_shaping() {
+        let bytes = get_font_bytes();
+        let face = Face::parse(&bytes, 0).unwrap();
+
+        let glyphs = shape(&face, "ab", rustybuzz::Direction::LeftToRight);
+        assert_eq!(glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>(), [0, 1]);
+        assert!(glyphs.iter().all(|glyph| glyph.advance == 600));
+
+        // a combining mark is composed with, or positioned over, the base letter
+        let glyphs = shape(&face, "e\u{301}", rustybuzz::Direction::LeftToRight);
+        assert!(glyphs.iter().all(|glyph| glyph.cluster == 0));
+        assert_eq!(glyphs.iter().map(|glyph| glyph.advance).sum::<i32>(), 600);
+
+        let text_mesh = TextMesh::new_no_font("e\u{301}x");
+        let glyphs = layout_text(&text_mesh, &[section(&text_mesh.text, &face)], 1.).glyphs;
+        assert_eq!(glyphs.last().unwrap().index, 2);
+        assert!((glyphs.last().unwrap().position.x - 0.6).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_bidi() {
+        let bytes = get_font_bytes();
+        let face = Face::parse(&bytes, 0).unwrap();
+
+        // chars in the visual order, left to right
+        let layout = |text_mesh: &TextMesh| {
+            let section = styled_section(&text_mesh.text, &face, &text_mesh.style);
+            layout_text(text_mesh, &[section], 1.).glyphs
+        };
+        let visual_order = |text_mesh: &TextMesh| {
+            let mut glyphs = layout(text_mesh);
+            glyphs.sort_by(|a, b| a.position.x.total_cmp(&b.position.x));
+            glyphs.iter().map(|glyph| glyph.index).collect::<Vec<_>>()
+        };
+
+        // a right-to-left word within left-to-right text
+        let mut text_mesh = sized_text("ab אב", 10., 10., TextMeshOverflow::Visible);
+        assert_eq!(visual_order(&text_mesh), [0, 1, 4, 3]);
+
+        // right-to-left paragraphs flow from the right, and the alignment is mirrored
+        text_mesh.text = "אב ab".into();
+        assert_eq!(visual_order(&text_mesh), [3, 4, 1, 0]);
+        let glyphs = layout(&text_mesh);
+        let right = glyphs
+            .iter()
+            .map(|glyph| glyph.position.x)
+            .fold(0., f32::max);
+        assert!((right + 0.6 - 10.).abs() < 1e-4);
+
+        // the base direction may be overridden
+        text_mesh.text = "ab".into();
+        text_mesh.style.direction = TextMeshDirection::RightToLeft;
+        assert_eq!(visual_order(&text_mesh), [0, 1]);
+        let glyphs = layout(&text_mesh);
+        assert!((glyphs[1].position.x + 0.6 - 10.).abs() < 1e-4);
+
+        // paired punctuation is mirrored in right-to-left runs
+        let glyphs = shape(&face, "(", rustybuzz::Direction::RightToLeft);
+        assert_eq!(Some(glyphs[0].glyph_id), face.glyph_index(')'));
+    }
+
+    #[test]
+    fn test_vertical() {
+        let bytes = get_font_bytes();
+        let face = Face::parse(&bytes, 0).unwrap();
+
+        let layout = |text: &str| {
+            let mut text_mesh = sized_text(text, 10., 2.5, TextMeshOverflow::Visible);
+            text_mesh.style.writing_mode = TextMeshWritingMode::Vertical;
+            text_mesh.alignment.horizontal = HorizontalAlign::Right;
+
+            let section = styled_section(text, &face, &text_mesh.style);
+            layout_text(&text_mesh, &[section], 1.).glyphs
+        };
+
+        // latin is rotated sideways, advancing downwards, and centered on the
+        // em wide column at the right edge by the middle of the ascender and descender
+        let glyphs = layout("ab");
+        assert!(glyphs.iter().all(|glyph| glyph.rotated));
+        assert!((glyphs[0].position - Vec2::new(9.5 - 0.335, 0.)).length() < 1e-4);
+        assert!((glyphs[1].position - Vec2::new(9.5 - 0.335, -0.6)).length() < 1e-4);
+
+        // ideographs are upright, advancing by the vertical advance (without a
+        // vmtx table, the ascender - descender), and wrap to the next column on the left
+        let glyphs = layout("漢字漢");
+        assert!(glyphs.iter().all(|glyph| !glyph.rotated));
+        assert!((glyphs[1].position.y - glyphs[0].position.y + 1.2).abs() < 1e-4);
+        assert_eq!(glyphs[0].position.x, glyphs[1].position.x);
+        assert!((glyphs[0].position.x - glyphs[2].position.x - 1.).abs() < 1e-4);
+        assert_eq!(glyphs[0].position.y, glyphs[2].position.y);
+
+        // the ellipsis of an upright column is upright, with the vertical advance
+        let mut text_mesh = sized_text("漢字漢", 10., 2.5, TextMeshOverflow::Ellipsis);
+        text_mesh.size.wrapping = false;
+        text_mesh.style.writing_mode = TextMeshWritingMode::Vertical;
+
+        let section = styled_section(&text_mesh.text, &face, &text_mesh.style);
+        let glyphs = layout_text(&text_mesh, &[section], 1.).glyphs;
+        assert_eq!(glyphs.len(), 2);
+        assert!(glyphs.iter().all(|glyph| !glyph.rotated));
+        assert_eq!(glyphs[1].glyph_id, face.glyph_index('…').unwrap());
+
+        // positions include the offsets from the vertical glyph origins
+        let offset = |text| shape(&face, text, rustybuzz::Direction::TopToBottom)[0].offset;
+        let pen = |i: usize, text| glyphs[i].position.y - offset(text).y as f32 / 1000.;
+        assert!((pen(0, "漢") - pen(1, "…") - 1.2).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_fit_font_size() {
+        let bytes = get_font_bytes();
+        let face = Face::parse(&bytes, 0).unwrap();
+
+        // limited by the width: five 0.6em advances into 6 units
+        let mut text_mesh = sized_text("hello", 6., 10., TextMeshOverflow::Visible);
+        let scalar = fit_font_size(&text_mesh, &[section(&text_mesh.text, &face)]);
+        assert!((scalar - 2.).abs() < 0.01);
+
+        text_mesh.style.font_size_bounds.max = Some(SizeUnit::NonStandard(72.));
+        let scalar = fit_font_size(&text_mesh, &[section(&text_mesh.text, &face)]);
+        assert!((scalar - 1.).abs() < 1e-4);
+
+        // limited by the height: one 1.2em line into 1.2 units
+        let text_mesh = sized_text("hello", 100., 1.2, TextMeshOverflow::Visible);
+        let scalar = fit_font_size(&text_mesh, &[section(&text_mesh.text, &face)]);
+        assert!((scalar - 1.).abs() < 0.01);
+
+        // wraps rather than shrinks
+        let text_mesh = sized_text("hello world", 3., 10., TextMeshOverflow::Visible);
+        let scalar = fit_font_size(&text_mesh, &[section(&text_mesh.text, &face)]);
+        assert!((scalar - 1.).abs() < 0.01);
+    }
+}
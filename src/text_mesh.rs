@@ -1,5 +1,4 @@
 use bevy::prelude::*;
-use glyph_brush_layout::{HorizontalAlign, VerticalAlign};
 
 use crate::{mesh_system::TextMeshState, TextMeshFont};
 pub use ttf2mesh::Quality;
@@ -19,16 +18,45 @@ pub struct TextMeshBundle {
     pub text_mesh_state: TextMeshState,
 }
 
+/// Text mesh rendered through the 2d mesh pipeline with a [`ColorMaterial`],
+/// for 2d cameras
+#[derive(Default, Bundle, Debug)]
+pub struct TextMesh2dBundle {
+    /// Text mesh configuration
+    pub text_mesh: TextMesh,
+
+    /// Standard bevy [`Transform`] for positioning the mesh
+    pub transform: Transform,
+
+    /// Standard bevy [`GlobalTransform`]
+    pub global_transform: GlobalTransform,
+
+    /// Marker for the 2d render path
+    pub text_mesh_2d: TextMesh2d,
+
+    /// Internal mesh state, no public API
+    pub text_mesh_state: TextMeshState,
+}
+
+/// Marker component, which renders the text mesh with a [`ColorMaterial`]
+/// through the 2d mesh pipeline instead of a [`StandardMaterial`]
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct TextMesh2d;
+
 /// Text mesh configuration
-#[derive(Debug, Component)]
+#[derive(Debug, Clone, PartialEq, Component)]
 pub struct TextMesh {
     /// Text string to be displayed
     pub text: String,
 
+    /// Styled sections of text, laid out together after each other.
+    /// When not empty, these are displayed instead of [`TextMesh::text`]
+    pub sections: Vec<TextMeshSection>,
+
     /// Text styling options (incl. font size)
     pub style: TextMeshStyle,
 
-    /// Text alignment options (**not implemented**)
+    /// Text alignment within the [`TextMeshSize`] box
     pub alignment: TextMeshAlignment,
 
     /// Text mesh container sizing
@@ -39,6 +67,7 @@ impl Default for TextMesh {
     fn default() -> Self {
         Self {
             text: "Hello World".into(),
+            sections: Vec::new(),
             size: TextMeshSize::default(),
             style: TextMeshStyle::default(),
             alignment: Default::default(),
@@ -66,6 +95,40 @@ impl TextMesh {
         }
     }
 
+    /// Text mesh of multiple sections, styled with the `font` unless the
+    /// sections override it
+    pub fn from_sections(
+        sections: impl IntoIterator<Item = TextMeshSection>,
+        font: Handle<TextMeshFont>,
+    ) -> Self {
+        Self {
+            text: String::new(),
+            sections: sections.into_iter().collect(),
+            style: TextMeshStyle {
+                font,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Fonts used by the text mesh
+    pub(crate) fn fonts(&self) -> impl Iterator<Item = &Handle<TextMeshFont>> {
+        std::iter::once(&self.style.font)
+            .chain(
+                self.sections
+                    .iter()
+                    .filter_map(|section| section.style.font.as_ref()),
+            )
+            .chain(
+                self.style
+                    .font_families
+                    .iter()
+                    .flat_map(|family| family.faces()),
+            )
+            .chain(&self.style.fallback_fonts)
+    }
+
     pub fn new_with_color<T: ToString>(text: T, font: Handle<TextMeshFont>, color: Color) -> Self {
         Self {
             text: text.to_string(),
@@ -79,15 +142,55 @@ impl TextMesh {
     }
 }
 
-const DEFAULT_FONT_SIZE: f32 = 36.;
+/// A run of text within a [`TextMesh`], e.g. a highlighted word
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextMeshSection {
+    pub value: String,
+    pub style: TextMeshSectionStyle,
+}
+
+impl TextMeshSection {
+    pub fn new<T: ToString>(value: T) -> Self {
+        Self {
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.style.color = Some(color);
+        self
+    }
+}
+
+/// Section specific styling. Unset options fall back to the [`TextMeshStyle`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextMeshSectionStyle {
+    pub font: Option<Handle<TextMeshFont>>,
+
+    /// Font size, [`SizeUnit::Em`] being relative to the [`TextMeshStyle`] font size
+    pub font_size: Option<SizeUnit>,
+    pub font_style: Option<FontStyle>,
+
+    /// Vertex color of the section, tinted by the [`TextMeshStyle`] color
+    pub color: Option<Color>,
+}
+
+pub(crate) const DEFAULT_FONT_SIZE: f32 = 36.;
 
-#[derive(Clone, Debug)]
+/// The default font size in world units
+pub(crate) const DEFAULT_FONT_SCALAR: f32 = DEFAULT_FONT_SIZE / 72.;
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct TextMeshSize {
     pub width: SizeUnit,
     pub height: SizeUnit,
+
+    /// Extrusion depth, or `None` for a flat mesh. [`SizeUnit::Auto`] is not
+    /// supported, and falls back to 0.05em
     pub depth: Option<SizeUnit>,
     pub wrapping: bool,
-    pub overflow: bool,
+    pub overflow: TextMeshOverflow,
 }
 
 impl Default for TextMeshSize {
@@ -95,20 +198,88 @@ impl Default for TextMeshSize {
         Self {
             width: SizeUnit::NonStandard(DEFAULT_FONT_SIZE * 10.),
             height: SizeUnit::NonStandard(DEFAULT_FONT_SIZE * 5.),
-            depth: Some(SizeUnit::NonStandard(DEFAULT_FONT_SIZE * 0.10)),
+            depth: Some(SizeUnit::NonStandard(DEFAULT_FONT_SIZE * 0.05)),
             wrapping: true,
-            overflow: false,
+            overflow: TextMeshOverflow::default(),
         }
     }
 }
 
-#[derive(Clone, Debug)]
+/// Handling of text that does not fit into the [`TextMeshSize`] box
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextMeshOverflow {
+    /// Text extends outside of the box
+    #[default]
+    Visible,
+
+    /// Lines that do not fit into the height, and glyphs that do not fit into the width, are dropped
+    Clip,
+
+    /// As [`TextMeshOverflow::Clip`], but the cut lines end with an ellipsis (…)
+    Ellipsis,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct TextMeshStyle {
     pub font: Handle<TextMeshFont>,
     pub font_size: SizeUnit,
     pub font_style: FontStyle,
     pub color: Color,
     pub mesh_quality: Quality,
+
+    /// Distance between baselines, as a multiple of the font's natural
+    /// line height (ascender - descender + line gap)
+    pub line_height: f32,
+
+    /// Limits for the [`SizeUnit::Auto`] font size
+    pub font_size_bounds: FontSizeBounds,
+
+    /// Vertex colors of the mesh, tinted by the `color` of the material
+    pub vertex_colors: TextMeshColors,
+
+    /// Texture coordinates of the front (and back) faces. The side walls are
+    /// projected by their x position (y, for walls facing sideways) and depth
+    pub uv_mode: TextMeshUvMode,
+
+    /// Maps the front and back faces to the upper half of the texture, and
+    /// the side walls to the lower half
+    pub uv_side_walls: bool,
+
+    /// Generates MikkTSpace tangents, needed by normal mapped materials
+    pub generate_tangents: bool,
+
+    /// Synthesized [`FontStyle::BOLD`] and [`FontStyle::ITALIC`]
+    pub synthesis: FontSynthesis,
+
+    /// Font families of the fonts in use. Bold and italic text of a family
+    /// font is displayed with the matching face, and synthesized only if the
+    /// family has no such face
+    pub font_families: Vec<TextMeshFontFamily>,
+
+    /// Fonts for the chars missing from the font, tried in order
+    pub fallback_fonts: Vec<Handle<TextMeshFont>>,
+
+    /// Base direction of the paragraphs. Left and right alignments are
+    /// mirrored in right-to-left paragraphs
+    pub direction: TextMeshDirection,
+
+    /// Horizontal lines, or vertical columns
+    pub writing_mode: TextMeshWritingMode,
+
+    /// Tracking, added between the glyphs, but not after the last glyph of a line.
+    /// [`SizeUnit::Em`] is relative to the font size of the section, negative
+    /// values tighten the text
+    pub letter_spacing: SizeUnit,
+
+    /// Added to the spaces between words, on top of the letter spacing
+    pub word_spacing: SizeUnit,
+
+    /// Distance between the tab stops, measured from the start of the line
+    pub tab_width: SizeUnit,
+
+    /// Displayed for the chars missing from all of the fonts. If none of the
+    /// fonts has this char either, the missing glyph box of the font is displayed
+    pub replacement_char: char,
 }
 
 impl Default for TextMeshStyle {
@@ -119,32 +290,242 @@ impl Default for TextMeshStyle {
             font_style: FontStyle::default(),
             color: Color::WHITE,
             mesh_quality: Quality::Medium,
+            line_height: 1.0,
+            font_size_bounds: FontSizeBounds::default(),
+            vertex_colors: TextMeshColors::default(),
+            uv_mode: TextMeshUvMode::default(),
+            uv_side_walls: false,
+            generate_tangents: false,
+            synthesis: FontSynthesis::default(),
+            font_families: Vec::new(),
+            fallback_fonts: Vec::new(),
+            replacement_char: char::REPLACEMENT_CHARACTER,
+            direction: TextMeshDirection::default(),
+            writing_mode: TextMeshWritingMode::default(),
+            letter_spacing: SizeUnit::Em(0.),
+            word_spacing: SizeUnit::Em(0.),
+            tab_width: SizeUnit::Em(2.),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// Source of the mesh vertex colors. Colors are applied with a single material,
+/// hence multicolored text needs only one draw call
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum TextMeshColors {
+    /// Colors of the [`TextMeshSection`]s, white if not set
+    #[default]
+    Sections,
+
+    /// Color per char of the text (of all sections), whitespace included.
+    /// Chars without a color use the section color
+    Chars(Vec<Color>),
+
+    /// Gradient from the left edge of the text to the right edge
+    HorizontalGradient(Color, Color),
+
+    /// Gradient from the top edge of the text to the bottom edge
+    VerticalGradient(Color, Color),
+}
+
+/// Parameters of the synthesized bold and italic styles
+#[derive(Clone, Debug, PartialEq)]
+pub struct FontSynthesis {
+    /// Outline dilation of bold glyphs in em units. The glyph advances grow
+    /// by twice the amount
+    pub embolden: f32,
+
+    /// Horizontal shear of italic glyphs, as x offset per y
+    pub skew: f32,
+}
+
+impl Default for FontSynthesis {
+    fn default() -> Self {
+        Self {
+            embolden: 0.02,
+            skew: 0.2,
+        }
+    }
+}
+
+/// Base direction of the text, for the Unicode bidirectional algorithm
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextMeshDirection {
+    /// Direction of each paragraph by its first strongly directional char,
+    /// left-to-right if none
+    #[default]
+    Auto,
+    LeftToRight,
+    RightToLeft,
+}
+
+/// Flow of the lines of text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextMeshWritingMode {
+    /// Lines from left to right (or right to left), stacked from top to bottom
+    #[default]
+    Horizontal,
+
+    /// Columns from top to bottom, stacked from right to left, e.g. for Japanese.
+    /// Columns wrap at the [`TextMeshSize::height`], and the ideographs and kana
+    /// are upright while the other scripts are rotated sideways. The
+    /// [`VerticalAlign`] aligns the text along the columns, and the
+    /// [`HorizontalAlign`] the block of columns
+    Vertical,
+}
+
+/// Faces of a font, selected by the [`FontStyle::BOLD`] and [`FontStyle::ITALIC`] flags
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextMeshFontFamily {
+    pub regular: Handle<TextMeshFont>,
+    pub bold: Option<Handle<TextMeshFont>>,
+    pub italic: Option<Handle<TextMeshFont>>,
+    pub bold_italic: Option<Handle<TextMeshFont>>,
+}
+
+impl TextMeshFontFamily {
+    pub fn new(regular: Handle<TextMeshFont>) -> Self {
+        Self {
+            regular,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_bold(mut self, bold: Handle<TextMeshFont>) -> Self {
+        self.bold = Some(bold);
+        self
+    }
+
+    pub fn with_italic(mut self, italic: Handle<TextMeshFont>) -> Self {
+        self.italic = Some(italic);
+        self
+    }
+
+    pub fn with_bold_italic(mut self, bold_italic: Handle<TextMeshFont>) -> Self {
+        self.bold_italic = Some(bold_italic);
+        self
+    }
+
+    pub(crate) fn faces(&self) -> impl Iterator<Item = &Handle<TextMeshFont>> {
+        std::iter::once(&self.regular)
+            .chain(self.bold.as_ref())
+            .chain(self.italic.as_ref())
+            .chain(self.bold_italic.as_ref())
+    }
+
+    /// Face for the font style, if the `font` is one of the family faces. The
+    /// returned style has the bold and italic flags which are left to synthesize
+    pub(crate) fn select(
+        &self,
+        font: &Handle<TextMeshFont>,
+        font_style: &FontStyle,
+    ) -> Option<(&Handle<TextMeshFont>, FontStyle)> {
+        if !self.faces().any(|face| face == font) {
+            return None;
+        }
+
+        let bold = font_style.contains(FontStyle::BOLD);
+        let italic = font_style.contains(FontStyle::ITALIC);
+
+        // closest faces first, the regular face is always available
+        let candidates = [
+            (bold && italic, &self.bold_italic, FontStyle::empty()),
+            (
+                bold,
+                &self.bold,
+                font_style.clone().intersection(FontStyle::ITALIC),
+            ),
+            (
+                italic,
+                &self.italic,
+                font_style.clone().intersection(FontStyle::BOLD),
+            ),
+        ];
+
+        let selected = candidates
+            .into_iter()
+            .find_map(|(wanted, face, synthesized)| match (wanted, face) {
+                (true, Some(face)) => Some((face, synthesized)),
+                _ => None,
+            })
+            .unwrap_or((
+                &self.regular,
+                font_style
+                    .clone()
+                    .intersection(FontStyle::BOLD | FontStyle::ITALIC),
+            ));
+
+        Some(selected)
+    }
+}
+
+/// Projection of the texture coordinates, with (0, 0) at the top left
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextMeshUvMode {
+    /// The texture spans over the whole text
+    #[default]
+    Block,
+
+    /// The texture spans over each glyph
+    Glyph,
+}
+
+/// With [`SizeUnit::Auto`] font size, the largest size at which the text fits
+/// into the [`TextMeshSize`] box is picked, but not outside of these bounds
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FontSizeBounds {
+    pub min: Option<SizeUnit>,
+    pub max: Option<SizeUnit>,
+}
+
+/// Size of the text, or of the text box.
+///
+/// For the font size, the size is the height of the em square: the
+/// capital letters are usually about 0.7em tall.
+#[derive(Debug, Clone, PartialEq)]
 pub enum SizeUnit {
+    /// Fit to the content. For the font size: the largest size that fits into the box
     Auto,
+
+    /// Legacy scale, where 72 units are one world unit
     NonStandard(f32),
-    //Px(f32),
-    //Em(f32),
-    //Cm(f32),
+
+    /// World units, meters by bevy convention
+    World(f32),
+
+    /// Typographic points (1/72 inch), with world units being meters
+    Pt(f32),
+
+    /// Centimeters, with world units being meters
+    Cm(f32),
+
+    /// Multiple of the font size. With [`SizeUnit::Auto`] font size (or
+    /// when used as the font size itself), relative to the default font size
+    Em(f32),
 }
 
 impl SizeUnit {
-    pub fn as_scalar(&self) -> Option<f32> {
+    /// Size in world units, `em` being the font size in world units
+    pub fn as_scalar(&self, em: f32) -> Option<f32> {
         match self {
             SizeUnit::Auto => None,
             SizeUnit::NonStandard(size) => Some(size / 72.),
+            SizeUnit::World(size) => Some(*size),
+            SizeUnit::Pt(size) => Some(size * 0.0254 / 72.),
+            SizeUnit::Cm(size) => Some(size / 100.),
+            SizeUnit::Em(size) => Some(size * em),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct TextMeshAlignment {
     pub vertical: VerticalAlign,
     pub horizontal: HorizontalAlign,
+
+    /// Point of the laid out box at the origin of the mesh, around which the
+    /// `Transform` rotates and scales the text
+    pub anchor: TextMeshAnchor,
 }
 
 impl Default for TextMeshAlignment {
@@ -152,17 +533,85 @@ impl Default for TextMeshAlignment {
         TextMeshAlignment {
             vertical: VerticalAlign::Top,
             horizontal: HorizontalAlign::Left,
+            anchor: TextMeshAnchor::default(),
+        }
+    }
+}
+
+/// Alignment of the lines within the width of the [`TextMeshSize`] box
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HorizontalAlign {
+    Left,
+    Center,
+    Right,
+
+    /// Lines spanning the width, by widening the spaces between the words.
+    /// The last line of each paragraph, and lines without spaces, are
+    /// aligned to the left (to the right in right-to-left paragraphs).
+    /// Vertical text is not justified
+    Justify,
+}
+
+/// Point of the laid out box at the origin of the mesh. The box is the
+/// [`TextMeshSize`] box, or the extent of the text when automatically sized
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TextMeshAnchor {
+    #[default]
+    TopLeft,
+    TopCenter,
+    TopRight,
+    CenterLeft,
+    Center,
+    CenterRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+
+    /// Left end of the baseline of the first line. In vertical text, the top
+    /// end of the center line of the first column
+    BaselineLeft,
+
+    /// Relative to the center of the box, in fractions of its size: (-0.5, -0.5)
+    /// is the bottom left corner and (0.5, 0.5) the top right, as with the
+    /// bevy sprite `Anchor`
+    Custom(Vec2),
+}
+
+impl TextMeshAnchor {
+    /// Position relative to the center of the box, in fractions of its size.
+    /// [`TextMeshAnchor::BaselineLeft`] depends on the font, and is at the
+    /// left center
+    pub fn as_vec(&self) -> Vec2 {
+        match self {
+            TextMeshAnchor::TopLeft => Vec2::new(-0.5, 0.5),
+            TextMeshAnchor::TopCenter => Vec2::new(0., 0.5),
+            TextMeshAnchor::TopRight => Vec2::new(0.5, 0.5),
+            TextMeshAnchor::CenterLeft | TextMeshAnchor::BaselineLeft => Vec2::new(-0.5, 0.),
+            TextMeshAnchor::Center => Vec2::ZERO,
+            TextMeshAnchor::CenterRight => Vec2::new(0.5, 0.),
+            TextMeshAnchor::BottomLeft => Vec2::new(-0.5, -0.5),
+            TextMeshAnchor::BottomCenter => Vec2::new(0., -0.5),
+            TextMeshAnchor::BottomRight => Vec2::new(0.5, -0.5),
+            TextMeshAnchor::Custom(anchor) => *anchor,
         }
     }
 }
 
+/// Alignment of the lines within the height of the [`TextMeshSize`] box
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VerticalAlign {
+    Top,
+    Center,
+    Bottom,
+}
+
 bitflags! {
-    #[derive(Clone, Debug)]
+    #[derive(Clone, Debug, PartialEq, Eq)]
     pub struct FontStyle: u32 {
-        const BOLD = 0b1; // TODO: implement - another font?
-        const ITALIC = 0b10; // TODO: implement - another font?
-        const UNDERLINE = 0b100; // TODO: implement
-        const STRIKETHROUGH = 0b1000; // TODO: implement
+        const BOLD = 0b1; // face of a `TextMeshFontFamily`, or synthesized
+        const ITALIC = 0b10; // face of a `TextMeshFontFamily`, or synthesized
+        const UNDERLINE = 0b100;
+        const STRIKETHROUGH = 0b1000;
         const LOWERCASE = 0b10000;
         const UPPERCASE = 0b100000;
     }